@@ -0,0 +1,90 @@
+//! This benchmark compares the ways of coloring a 4K map with a
+//! biome map: the color evaluator, the color evaluator behind a lock
+//! to emulate evaluators that lock on every pixel, and a precomputed
+//! lookup table.
+//!
+//! Run it with `cargo bench --bench color`.
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use ficture::cell::Cell;
+use ficture::config::Config;
+use ficture::map::{Map, MapMonad};
+
+const WIDTH: usize = 3840;
+const HEIGHT: usize = 2160;
+const RUNS: u32 = 5;
+
+/// Creates a 4K map with elevation and moisture covering the
+/// whole biome map.
+fn map() -> Map<Cell> {
+    Map::return_single(
+        Cell {
+            elevation: 0.0,
+            moisture: 0.0,
+        },
+        WIDTH,
+        HEIGHT,
+    )
+    .and_then_with_coordinates(|_, x, y| Cell {
+        elevation: x as f64 / WIDTH as f64,
+        moisture: y as f64 / HEIGHT as f64,
+    })
+}
+
+/// Gets the average time taken to color a 4K map with `f`.
+fn time<F>(f: F) -> Duration
+where
+    F: Fn(&Cell) -> image::Rgba<u8> + Send + Sync,
+{
+    let mut total = Duration::ZERO;
+
+    for _ in 0..RUNS {
+        let map = map();
+        let start = Instant::now();
+        let colors = map.and_then(|cell| f(&cell));
+
+        total += start.elapsed();
+        assert_eq!(colors.len(), WIDTH * HEIGHT);
+    }
+
+    total / RUNS
+}
+
+fn main() {
+    let config = Config::from_file("config/config.yaml").expect("config to load");
+    let evaluator = config
+        .get_color_evaluator("default")
+        .expect("default color evaluator to exist");
+
+    let locked_evaluator = Mutex::new(&evaluator);
+    let locked = time(|cell| {
+        locked_evaluator
+            .lock()
+            .expect("failed to acquire lock")
+            .evaluate(cell.elevation, cell.moisture)
+    });
+    let lock_free = time(|cell| evaluator.evaluate(cell.elevation, cell.moisture));
+
+    let start = Instant::now();
+    let lookup_table = evaluator.lookup_table(1024);
+    let build = start.elapsed();
+    let table = time(|cell| lookup_table.evaluate(cell.elevation, cell.moisture));
+
+    println!(
+        "coloring a {}x{} map, averaged over {} runs",
+        WIDTH, HEIGHT, RUNS
+    );
+    println!("locked evaluator:    {:>10.2?}", locked);
+    println!(
+        "lock-free evaluator: {:>10.2?} ({:.1}x)",
+        lock_free,
+        locked.as_secs_f64() / lock_free.as_secs_f64()
+    );
+    println!(
+        "lookup table:        {:>10.2?} ({:.1}x, plus {:.2?} to build)",
+        table,
+        locked.as_secs_f64() / table.as_secs_f64(),
+        build
+    );
+}
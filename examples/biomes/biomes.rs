@@ -3,7 +3,7 @@
 //! elevation on the x axis and moisture on the y axis.
 use ficture::cell::Cell;
 use ficture::config::Config;
-use ficture::image::pixel_map_to_image;
+use ficture::image::pixel_map_to_rgba_image;
 use ficture::map::{Map, MapMonad};
 
 fn main() {
@@ -29,7 +29,7 @@ fn main() {
         moisture: y as f64 / height as f64,
     });
     let map = map.and_then(|cell| evaluator.evaluate(cell.elevation, cell.moisture));
-    let image = map.extract(pixel_map_to_image);
+    let image = map.extract(pixel_map_to_rgba_image);
 
     image.save("biomes.png").expect("image to save");
 }
@@ -1,11 +1,15 @@
 //! This module contains the structure containing all
 //! command line arguments.
-pub use clap::Parser;
+pub use clap::{Parser, Subcommand, ValueEnum};
 
 /// A structure containing all command line arguments.
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
+    /// What to do instead of generating a map.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// The width of generated maps.
     #[arg(long, default_value_t = 1920)]
     pub width: usize,
@@ -17,4 +21,282 @@ pub struct Args {
     /// The path to the config file to use.
     #[arg(long, short, default_value_t = String::from("config/config.yaml"))]
     pub filepath: String,
+
+    /// The path to save the map to.
+    #[arg(long, short, default_value_t = String::from("image.png"))]
+    pub output: String,
+
+    /// Replace the seeds of every noise generator and of the plate
+    /// tectonics in the config file with this seed.
+    #[arg(long)]
+    pub seed: Option<u32>,
+
+    /// Save a bundle of layers to this directory, along with a
+    /// manifest describing them, instead of saving only the map.
+    #[arg(long)]
+    pub bundle: Option<String>,
+
+    /// The layers to save in the bundle, separated by commas.
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [
+            BundleLayer::Map,
+            BundleLayer::Elevation,
+            BundleLayer::Moisture,
+            BundleLayer::Biomes,
+            BundleLayer::Hillshade,
+        ]
+    )]
+    pub bundle_layers: Vec<BundleLayer>,
+
+    /// What determines the depth of the ocean when coloring it.
+    #[arg(long, value_enum, default_value_t = OceanDepth::Elevation)]
+    pub ocean_depth: OceanDepth,
+
+    /// Color the map from a lookup table with this many steps along
+    /// elevation and moisture, rather than evaluating every cell.
+    #[arg(long)]
+    pub color_lookup: Option<usize>,
+
+    /// Sink any islands with fewer cells than this below the sea.
+    #[arg(long)]
+    pub min_island_area: Option<usize>,
+
+    /// The minimum number of continents that generated maps should
+    /// have. Continents must be at least as large as the minimum
+    /// island area.
+    #[arg(long)]
+    pub continents: Option<usize>,
+
+    /// Save the map with an alpha channel, keeping the transparency
+    /// of any gradients with transparent colors.
+    #[arg(long)]
+    pub alpha: bool,
+
+    /// Leave the ocean transparent so that the map can be laid over
+    /// other artwork. Implies --alpha.
+    #[arg(long)]
+    pub transparent_ocean: bool,
+
+    /// Also save the hillshade of the terrain on its own as a
+    /// grayscale image at this path.
+    #[arg(long)]
+    pub hillshade_image: Option<String>,
+
+    /// Also save a tangent-space normal map of the terrain at this
+    /// path.
+    #[arg(long)]
+    pub normal_map: Option<String>,
+
+    /// Also save the slope of the terrain as a grayscale image at
+    /// this path, from black for flat ground to white for cliffs.
+    #[arg(long)]
+    pub slope_image: Option<String>,
+
+    /// Also save the direction that the terrain faces as a grayscale
+    /// image at this path, from black facing north clockwise to white.
+    #[arg(long)]
+    pub aspect_image: Option<String>,
+
+    /// How much the elevation is stretched for the normal map and the
+    /// slope image.
+    #[arg(long, default_value_t = 50.0)]
+    pub terrain_exaggeration: f64,
+
+    /// Also export the contour lines as GeoJSON at this path.
+    #[arg(long)]
+    pub contours_geojson: Option<String>,
+
+    /// Also save the elevation as a grayscale heightmap at this path.
+    #[arg(long)]
+    pub heightmap: Option<String>,
+
+    /// The number of bits used for each pixel of the heightmap.
+    #[arg(long, value_enum, default_value_t = HeightmapDepth::Sixteen)]
+    pub heightmap_depth: HeightmapDepth,
+
+    /// The elevation that is black in the heightmap.
+    #[arg(long, default_value_t = 0.0)]
+    pub heightmap_min: f64,
+
+    /// The elevation that is white in the heightmap.
+    #[arg(long, default_value_t = 1.0)]
+    pub heightmap_max: f64,
+
+    /// Measure the elevation of the heightmap from the sea level
+    /// rather than from the deepest ocean.
+    #[arg(long)]
+    pub heightmap_from_sea_level: bool,
+
+    /// Also save the elevation as a floating point raster at this
+    /// path. The format is chosen by the extension, which can be
+    /// tif, tiff, exr or pfm.
+    #[arg(long)]
+    pub elevation_raster: Option<String>,
+
+    /// Also save the moisture as a floating point raster at this
+    /// path. The format is chosen by the extension, which can be
+    /// tif, tiff, exr or pfm.
+    #[arg(long)]
+    pub moisture_raster: Option<String>,
+
+    /// Also save the map as an SVG image at this path, with the
+    /// biomes traced into polygons and the coastline, rivers and
+    /// contours drawn as paths.
+    #[arg(long)]
+    pub svg: Option<String>,
+
+    /// How many units wide each cell is in the SVG image.
+    #[arg(long, default_value_t = 1.0)]
+    pub svg_scale: f64,
+
+    /// The share of the cells of the map that must drain through a
+    /// cell for a river to be drawn through it in the SVG image. Set
+    /// it to 1 to leave rivers out.
+    #[arg(long, default_value_t = 0.0005)]
+    pub svg_river_flow: f64,
+
+    /// Also save the terrain as a 3D mesh at this path. The format
+    /// is chosen by the extension, which can be obj, stl or glb.
+    #[arg(long)]
+    pub mesh: Option<String>,
+
+    /// How tall the highest possible elevation of the mesh is, where
+    /// each cell is 1 wide.
+    #[arg(long, default_value_t = 100.0)]
+    pub mesh_scale: f64,
+
+    /// How many cells apart the vertices of the mesh are.
+    #[arg(long, default_value_t = 1)]
+    pub mesh_decimation: usize,
+
+    /// Close the mesh with a base this far below the lowest possible
+    /// elevation so that it can be 3D printed.
+    #[arg(long, default_value_t = 0.0)]
+    pub mesh_base: f64,
+
+    /// Wrap the mesh onto a sphere.
+    #[arg(long)]
+    pub mesh_sphere: bool,
+
+    /// Also print a preview of the map to the terminal.
+    #[arg(long)]
+    pub preview: bool,
+
+    /// Preview the elevation as ASCII characters rather than the map
+    /// in color, for terminals without color.
+    #[arg(long)]
+    pub preview_ascii: bool,
+
+    /// How many columns wide the preview is. Defaults to the width of
+    /// the terminal.
+    #[arg(long)]
+    pub preview_width: Option<usize>,
+}
+
+/// The commands that can be run instead of generating a map.
+#[derive(Subcommand)]
+pub enum Command {
+    /// Render a labeled legend of a biome map as an image.
+    Legend {
+        /// The path to save the legend to.
+        #[arg(long, short, default_value_t = String::from("legend.png"))]
+        output: String,
+
+        /// The name of the biome map in the config file.
+        #[arg(long, default_value_t = String::from("default"))]
+        biome_map: String,
+
+        /// The width and height of the chart in pixels.
+        #[arg(long, default_value_t = 256)]
+        chart_size: u32,
+
+        /// How many pixels wide each pixel of the labels is drawn.
+        #[arg(long, default_value_t = 2)]
+        font_scale: u32,
+    },
+    /// Print how a map was generated from the metadata in its PNG and
+    /// recover the config used to generate it.
+    Inspect {
+        /// The path to the PNG to inspect.
+        path: String,
+
+        /// Save the recovered config to this path rather than printing
+        /// it.
+        #[arg(long, short)]
+        config: Option<String>,
+    },
+    /// Render a pyramid of `z/x/y.png` tiles of the map along with an
+    /// HTML viewer for browsing them.
+    Tiles {
+        /// The directory to save the tiles to.
+        #[arg(long, short, default_value_t = String::from("tiles"))]
+        output: String,
+
+        /// The highest zoom level to render. Every zoom level doubles
+        /// the resolution of the last.
+        #[arg(long, default_value_t = 3)]
+        max_zoom: u32,
+
+        /// The width and height of every tile in pixels.
+        #[arg(long, default_value_t = 256)]
+        tile_size: usize,
+    },
+    /// Serve tiles of the map over HTTP, rendering them on demand and
+    /// reloading them whenever the config file changes.
+    Serve {
+        /// The address to listen on.
+        #[arg(long, short, default_value_t = String::from("127.0.0.1:8080"))]
+        address: String,
+
+        /// The highest zoom level to render.
+        #[arg(long, default_value_t = 8)]
+        max_zoom: u32,
+
+        /// The width and height of every tile in pixels.
+        #[arg(long, default_value_t = 256)]
+        tile_size: usize,
+
+        /// How many rendered tiles to keep in memory.
+        #[arg(long, default_value_t = 1024)]
+        cache_size: usize,
+    },
+}
+
+/// The number of bits used for each pixel of a heightmap.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum HeightmapDepth {
+    /// 8 bits per pixel.
+    #[value(name = "8")]
+    Eight,
+    /// 16 bits per pixel.
+    #[value(name = "16")]
+    Sixteen,
+}
+
+/// The layers that can be saved in a bundle.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum BundleLayer {
+    /// The colored map.
+    Map,
+    /// The elevation as a 16 bit heightmap.
+    Elevation,
+    /// The moisture as a 16 bit grayscale image.
+    Moisture,
+    /// The identifier of the biome of every cell as a 16 bit
+    /// grayscale image.
+    Biomes,
+    /// The hillshade of the terrain.
+    Hillshade,
+}
+
+/// The ways that the depth of the ocean can be determined.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OceanDepth {
+    /// Use the elevation of the ocean floor.
+    Elevation,
+    /// Use the distance from the nearest coastline.
+    Distance,
 }
@@ -1,18 +1,355 @@
+use ficture::bundle::{hash_bytes, ManifestBuilder};
 use ficture::cell::Cell;
+use ficture::color::{ColorEvaluator, ColorFunc};
 use ficture::config::Config;
-use ficture::image::pixel_map_to_image;
+use ficture::contour::{contours_to_geojson, ContourGenerator, ContourLine};
+use ficture::distance::distance_transform;
+use ficture::heightmap::{BitDepth, HeightmapExporterBuilder};
+use ficture::image::{
+    biome_map_to_image, pixel_map_to_image, pixel_map_to_rgba_image, value_map_to_image,
+};
+use ficture::landmass::{ensure_continents, remove_small_islands};
+use ficture::legend::LegendBuilder;
 use ficture::map::{Map, MapMonad};
+use ficture::mesh::MeshGeneratorBuilder;
+use ficture::metadata::MapMetadata;
 use ficture::noise::SimplexNoiseGeneratorBuilder;
+use ficture::preview::{render_ascii, render_truecolor, supports_truecolor, terminal_width};
+use ficture::raster::RasterExporterBuilder;
+use ficture::river::trace_rivers;
+use ficture::server::TileServerBuilder;
+use ficture::svg::SvgExporterBuilder;
+use ficture::terrain::{aspect_map, normal_map, slope_map, HillshadeBuilder};
+use ficture::tiles::{export_tiles, TileRenderer};
 use ficture::utils::normalize;
 
 mod args;
 
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+
 use anyhow::Context;
-use args::{Args, Parser};
+use args::{Args, BundleLayer, Command, HeightmapDepth, OceanDepth, Parser};
+use image::{DynamicImage, Pixel, Rgba};
+
+/// The elevation below which cells are part of the ocean.
+const SEA_LEVEL: f64 = 0.05;
+
+/// Renders a labeled legend of a biome map and saves it to `output`.
+fn legend(
+    config: &Config,
+    output: &str,
+    biome_map: &str,
+    chart_size: u32,
+    font_scale: u32,
+) -> anyhow::Result<()> {
+    let evaluator = config
+        .get_color_evaluator(biome_map)
+        .with_context(|| format!("{biome_map} color evaluator not defined in config file"))?;
+    let mut builder = LegendBuilder::new()
+        .sea_level(SEA_LEVEL)
+        .chart_size(chart_size)
+        .font_scale(font_scale);
+
+    if let Some(ocean) = config.get_color_func("ocean") {
+        builder = builder.ocean(ocean);
+    }
+
+    let image = builder.build().render(&evaluator);
+
+    image.save(output).context("failed to save legend")?;
+
+    Ok(())
+}
+
+/// Prints the metadata of a PNG generated by ficture and saves or
+/// prints the config used to generate it.
+fn inspect(path: &str, config_path: Option<&str>) -> anyhow::Result<()> {
+    let metadata = MapMetadata::read_png(path).context("failed to read metadata")?;
+
+    println!("version: {}", metadata.version);
+
+    if let Some(seed) = metadata.seed {
+        println!("seed: {seed}");
+    }
+
+    println!("width: {}", metadata.width);
+    println!("height: {}", metadata.height);
+    println!("sea level: {}", metadata.sea_level);
+    println!("arguments: {:?}", metadata.arguments);
+
+    if let Some(config_hash) = metadata.config_hash {
+        println!("config hash: {config_hash:016x}");
+    }
+
+    match config_path {
+        Some(config_path) => {
+            std::fs::write(config_path, &metadata.config).context("failed to save config")?;
+            println!("config saved to {config_path}");
+        }
+        None => print!("config:\n{}", metadata.config),
+    }
+
+    Ok(())
+}
+
+/// Saves the layers of a map that were asked for to a bundle, along
+/// with the hillshade if there is one, and describes them in a
+/// manifest. The colored map is left to be added to the manifest once
+/// the map is colored.
+fn save_bundle(
+    args: &Args,
+    bundle: &Path,
+    config: &Config,
+    config_hash: u64,
+    evaluator: &ColorEvaluator,
+    map: &Map<Cell>,
+    shade: Option<&Map<f64>>,
+) -> anyhow::Result<ManifestBuilder> {
+    let in_bundle = |layer: BundleLayer| args.bundle_layers.contains(&layer);
+    let mut manifest = ManifestBuilder::new(args.width, args.height)
+        .seed(args.seed)
+        .config(&args.filepath, config_hash)
+        .sea_level(SEA_LEVEL);
+
+    for (generator, seed) in config.seeds() {
+        manifest = manifest.generator_seed(generator, seed);
+    }
+    for biome in evaluator.classifier().biomes() {
+        manifest = manifest.biome(biome.id, &biome.name);
+    }
+
+    std::fs::create_dir_all(bundle).context("failed to create bundle directory")?;
+
+    let exporter = HeightmapExporterBuilder::new().build();
+    let layers = [
+        (
+            BundleLayer::Elevation,
+            "elevation",
+            (|cell| cell.elevation) as fn(&Cell) -> f64,
+        ),
+        (BundleLayer::Moisture, "moisture", |cell| cell.moisture),
+    ];
+
+    for (layer, name, value) in layers {
+        if !in_bundle(layer) {
+            continue;
+        }
+
+        let file = format!("{name}.png");
+        let values = Map::from_vec(map.iter().map(value).collect(), map.width(), map.height());
+
+        exporter
+            .save(&values, bundle.join(&file))
+            .with_context(|| format!("failed to save {name} to bundle"))?;
+        manifest = manifest.file(
+            name,
+            file,
+            format!("the {name} from 0-1 as a 16 bit grayscale image"),
+        );
+    }
+    if in_bundle(BundleLayer::Biomes) {
+        let ids = evaluator.classifier().classify_map(map, SEA_LEVEL);
+        let image = ids.extract(biome_map_to_image);
+
+        image
+            .save(bundle.join("biomes.png"))
+            .context("failed to save biomes to bundle")?;
+        manifest = manifest.file(
+            "biomes",
+            "biomes.png",
+            "the identifier of the biome of every cell as a 16 bit grayscale image, with 0 for the ocean",
+        );
+    }
+    if let (true, Some(shade)) = (in_bundle(BundleLayer::Hillshade), shade) {
+        let image = value_map_to_image(shade.to_vec(), shade.width(), shade.height());
+
+        image
+            .save(bundle.join("hillshade.png"))
+            .context("failed to save hillshade to bundle")?;
+        manifest = manifest.file(
+            "hillshade",
+            "hillshade.png",
+            "how brightly the terrain is lit as a grayscale image",
+        );
+    }
+
+    Ok(manifest)
+}
+
+/// Saves the layers of a map that were asked for as floating point
+/// rasters, with the parameters of the run as their metadata.
+fn save_rasters(
+    args: &Args,
+    config: &Config,
+    config_hash: u64,
+    map: &Map<Cell>,
+) -> anyhow::Result<()> {
+    let rasters = [
+        (
+            "elevation",
+            &args.elevation_raster,
+            (|cell| cell.elevation) as fn(&Cell) -> f64,
+        ),
+        ("moisture", &args.moisture_raster, |cell| cell.moisture),
+    ];
+
+    for (name, path, value) in rasters {
+        let Some(path) = path else {
+            continue;
+        };
+        let layer = Map::from_vec(map.iter().map(value).collect(), map.width(), map.height());
+        let mut exporter = RasterExporterBuilder::new()
+            .name(name)
+            .parameter("config", &args.filepath)
+            .parameter("config_hash", format!("{config_hash:016x}"))
+            .parameter("width", args.width)
+            .parameter("height", args.height)
+            .parameter("sea_level", SEA_LEVEL);
+
+        for (generator, seed) in config.seeds() {
+            exporter = exporter.parameter(format!("seed.{generator}"), seed);
+        }
+
+        let exporter = exporter.build();
+
+        exporter
+            .save(&layer, path)
+            .with_context(|| format!("failed to save {name} raster"))?;
+    }
+
+    Ok(())
+}
+
+/// Saves a map as an SVG, filling each biome with the middle of its
+/// gradient and leaving the ocean to the background. Rivers are drawn
+/// in the color of the ocean.
+fn save_svg(
+    args: &Args,
+    path: &str,
+    evaluator: &ColorEvaluator,
+    ocean: &ColorFunc,
+    map: &Map<Cell>,
+    contours: Option<&(ContourGenerator, Vec<ContourLine>)>,
+) -> anyhow::Result<()> {
+    let classifier = evaluator.classifier();
+    let biomes = classifier.classify_map(map, SEA_LEVEL);
+    let elevation = Map::from_vec(
+        map.iter().map(|cell| cell.elevation).collect(),
+        map.width(),
+        map.height(),
+    );
+    let min_flow = (elevation.len() as f64 * args.svg_river_flow)
+        .ceil()
+        .max(1.0) as usize;
+    let rivers = trace_rivers(&elevation, SEA_LEVEL, min_flow);
+    let mut builder = SvgExporterBuilder::new()
+        .scale(args.svg_scale)
+        .sea_level(SEA_LEVEL)
+        .ocean(ocean(0.5))
+        .river(ocean(0.5), 1.5);
+
+    for biome in classifier.biomes() {
+        builder = builder.biome(
+            biome.id,
+            &biome.name,
+            evaluator.gradient_color(biome.id, 0.5),
+        );
+    }
+    if let Some((generator, _)) = contours {
+        builder = builder.contour_colors(generator.color(false), generator.color(true));
+    }
+
+    let contours = contours
+        .map(|(_, contours)| contours.as_slice())
+        .unwrap_or_default();
+
+    builder
+        .build()
+        .save(&biomes, &elevation, contours, &rivers, path)
+        .context("failed to save SVG")
+}
+
+/// Saves the elevation of a map as a mesh, with its vertices colored
+/// by the colored map.
+fn save_mesh(
+    args: &Args,
+    path: &str,
+    surface: &Map<f64>,
+    colors: &Map<Rgba<u8>>,
+) -> anyhow::Result<()> {
+    let generator = MeshGeneratorBuilder::new()
+        .vertical_scale(args.mesh_scale)
+        .decimation(args.mesh_decimation)
+        .base(args.mesh_base)
+        .sphere(args.mesh_sphere)
+        .build();
+    let mesh = generator.generate(surface, Some(colors));
+
+    mesh.save(path).context("failed to save mesh")
+}
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
-    let config = Config::from_file(args.filepath).context("config file path not provided")?;
+
+    if let Some(Command::Inspect { path, config }) = &args.command {
+        return inspect(path, config.as_deref());
+    }
+
+    if let Some(Command::Serve {
+        address,
+        max_zoom,
+        tile_size,
+        cache_size,
+    }) = &args.command
+    {
+        let server = TileServerBuilder::new(&args.filepath)
+            .seed(args.seed)
+            .sea_level(SEA_LEVEL)
+            .tile_size(*tile_size)
+            .max_zoom(*max_zoom)
+            .cache_size(*cache_size)
+            .on_event(|event| eprintln!("{event}"))
+            .build();
+        let listener = TcpListener::bind(address).context("failed to listen for connections")?;
+
+        println!("serving tiles at http://{}", listener.local_addr()?);
+
+        return server.serve(listener).context("failed to serve tiles");
+    }
+
+    let config_bytes = std::fs::read(&args.filepath).context("failed to read config file")?;
+    // the hash is taken from the bytes that are parsed so that it
+    // always describes the config that the map is generated from
+    let config_hash = hash_bytes(&config_bytes);
+    let mut config = Config::from_slice(&config_bytes).context("failed to load config file")?;
+
+    config.validate().context("invalid config file")?;
+
+    if let Some(seed) = args.seed {
+        config.reseed(seed);
+    }
+
+    if let Some(Command::Legend {
+        output,
+        biome_map,
+        chart_size,
+        font_scale,
+    }) = &args.command
+    {
+        return legend(&config, output, biome_map, *chart_size, *font_scale);
+    }
+    if let Some(Command::Tiles {
+        output,
+        max_zoom,
+        tile_size,
+    }) = &args.command
+    {
+        let renderer = TileRenderer::new(config, SEA_LEVEL, *tile_size)
+            .context("failed to create tile renderer")?;
+
+        return export_tiles(&renderer, output, *max_zoom).context("failed to save tiles");
+    }
 
     let elevation_noise_generator = config
         .get_noise_generator::<SimplexNoiseGeneratorBuilder>(
@@ -34,7 +371,7 @@ fn main() -> anyhow::Result<()> {
     let ocean = config
         .get_color_func("ocean")
         .context("ocean gradient not defined in config file")?;
-    let sea_level = 0.05;
+    let sea_level = SEA_LEVEL;
 
     let map: Map<Cell> = Map::return_single(
         Cell {
@@ -75,20 +412,356 @@ fn main() -> anyhow::Result<()> {
         }
     });
 
-    let map = map.and_then(|cell| {
+    // mix in elevation from plate tectonics
+    let map = if let Some(tectonics) =
+        config.get_tectonics("elevation_tectonics", args.width, args.height)
+    {
+        map.and_then_with_coordinates(|cell, x, y| Cell {
+            elevation: tectonics.apply(cell.elevation, x, y),
+            moisture: cell.moisture,
+        })
+    } else {
+        map
+    };
+
+    // shape the elevation into islands or continents with a mask
+    let map = if let Some(mask) = config
+        .get_mask("elevation_mask", args.width, args.height)
+        .context("failed to load elevation_mask")?
+    {
+        map.and_then_with_coordinates(|cell, x, y| Cell {
+            elevation: mask.apply(cell.elevation, x, y),
+            moisture: cell.moisture,
+        })
+    } else {
+        map
+    };
+
+    // reshape the landmasses, which only changes the elevation
+    let map = if args.min_island_area.is_some() || args.continents.is_some() {
+        let min_area = args.min_island_area.unwrap_or(1);
+        let mut elevation = Map::from_vec(
+            map.iter().map(|cell| cell.elevation).collect(),
+            map.width(),
+            map.height(),
+        );
+
+        if let Some(count) = args.continents {
+            elevation = ensure_continents(elevation, sea_level, count, min_area);
+        }
+        if args.min_island_area.is_some() {
+            elevation = remove_small_islands(elevation, sea_level, min_area);
+        }
+
+        map.and_then_with_coordinates(|cell, x, y| Cell {
+            elevation: *elevation.get(x, y),
+            moisture: cell.moisture,
+        })
+    } else {
+        map
+    };
+
+    // replace the noise based moisture with moisture carried in from
+    // the oceans when a moisture model is configured
+    let map = if let Some(moisture_model) = config.get_moisture_model("moisture_model") {
+        let elevation = Map::from_vec(
+            map.iter().map(|cell| cell.elevation).collect(),
+            map.width(),
+            map.height(),
+        );
+        let moisture = moisture_model.simulate(&elevation, sea_level);
+
+        map.and_then_with_coordinates(|cell, x, y| Cell {
+            elevation: cell.elevation,
+            moisture: *moisture.get(x, y),
+        })
+    } else {
+        map
+    };
+
+    // when the ocean is shaded by distance, shallow water hugs the
+    // coastline and gets deeper the further it is from land
+    let distance_to_land = match args.ocean_depth {
+        OceanDepth::Elevation => None,
+        OceanDepth::Distance => {
+            let land = Map::from_vec(
+                map.iter().map(|cell| cell.elevation >= sea_level).collect(),
+                map.width(),
+                map.height(),
+            );
+            let distance = distance_transform(&land);
+            let max_distance = distance
+                .iter()
+                .filter(|distance| distance.is_finite())
+                .fold(0.0, |acc: f64, distance| acc.max(*distance));
+
+            Some((distance, max_distance))
+        }
+    };
+
+    let in_bundle =
+        |layer: BundleLayer| args.bundle.is_some() && args.bundle_layers.contains(&layer);
+
+    // light the terrain to give it a sense of relief, treating the
+    // surface of the ocean as flat. only a configured hillshade is
+    // applied to the map, while the default one is used when the
+    // hillshade is only exported on its own
+    let (hillshade, shade_map) = match config.get_hillshade("hillshade") {
+        Some(hillshade) => (Some(hillshade), true),
+        None if args.hillshade_image.is_some() || in_bundle(BundleLayer::Hillshade) => {
+            (Some(HillshadeBuilder::new().build()), false)
+        }
+        None => (None, false),
+    };
+    let shade = hillshade.as_ref().map(|hillshade| {
+        let surface = Map::from_vec(
+            map.iter()
+                .map(|cell| cell.elevation.max(sea_level))
+                .collect(),
+            map.width(),
+            map.height(),
+        );
+
+        hillshade.shade(&surface)
+    });
+
+    if let (Some(path), Some(shade)) = (&args.hillshade_image, &shade) {
+        let image = value_map_to_image(shade.to_vec(), shade.width(), shade.height());
+
+        image.save(path).context("failed to save hillshade image")?;
+    }
+
+    // describe everything saved in the bundle in its manifest, which
+    // is finished once the map is colored
+    let mut manifest = match &args.bundle {
+        Some(bundle) => Some(save_bundle(
+            &args,
+            Path::new(bundle),
+            &config,
+            config_hash,
+            &evaluator,
+            &map,
+            shade.as_ref(),
+        )?),
+        None => None,
+    };
+
+    // export the shape of the terrain, again treating the surface of
+    // the ocean as flat
+    if args.normal_map.is_some() || args.slope_image.is_some() || args.aspect_image.is_some() {
+        let surface = Map::from_vec(
+            map.iter()
+                .map(|cell| cell.elevation.max(sea_level))
+                .collect(),
+            map.width(),
+            map.height(),
+        );
+        let exaggeration = args.terrain_exaggeration;
+
+        if let Some(path) = &args.normal_map {
+            let image = normal_map(&surface, exaggeration).extract(pixel_map_to_image);
+
+            image.save(path).context("failed to save normal map")?;
+        }
+        if let Some(path) = &args.slope_image {
+            let slope = slope_map(&surface, exaggeration);
+            let image = value_map_to_image(slope.to_vec(), slope.width(), slope.height());
+
+            image.save(path).context("failed to save slope image")?;
+        }
+        if let Some(path) = &args.aspect_image {
+            let aspect = aspect_map(&surface);
+            let image = value_map_to_image(aspect.to_vec(), aspect.width(), aspect.height());
+
+            image.save(path).context("failed to save aspect image")?;
+        }
+    }
+
+    // trace contour lines when they are configured
+    let contours = config.get_contours("contours", sea_level).map(|generator| {
+        let elevation = Map::from_vec(
+            map.iter().map(|cell| cell.elevation).collect(),
+            map.width(),
+            map.height(),
+        );
+        let contours = generator.generate(&elevation);
+
+        (generator, contours)
+    });
+
+    if let Some(path) = &args.contours_geojson {
+        let (_, contours) = contours
+            .as_ref()
+            .context("contours not defined in config file")?;
+
+        std::fs::write(path, contours_to_geojson(contours)).context("failed to save contours")?;
+    }
+
+    if let Some(path) = &args.heightmap {
+        let elevation = Map::from_vec(
+            map.iter().map(|cell| cell.elevation).collect(),
+            map.width(),
+            map.height(),
+        );
+        let bit_depth = match args.heightmap_depth {
+            HeightmapDepth::Eight => BitDepth::Eight,
+            HeightmapDepth::Sixteen => BitDepth::Sixteen,
+        };
+        let sea_level = if args.heightmap_from_sea_level {
+            sea_level
+        } else {
+            0.0
+        };
+        let exporter = HeightmapExporterBuilder::new()
+            .range(args.heightmap_min, args.heightmap_max)
+            .sea_level(sea_level)
+            .bit_depth(bit_depth)
+            .build();
+
+        exporter
+            .save(&elevation, path)
+            .context("failed to save heightmap")?;
+    }
+
+    save_rasters(&args, &config, config_hash, &map)?;
+
+    if let Some(path) = &args.svg {
+        save_svg(&args, path, &evaluator, &ocean, &map, contours.as_ref())?;
+    }
+
+    // keep the elevation for the mesh with the ocean flattened to its
+    // surface, since the vertices are colored once the map is colored
+    let mesh_surface = args.mesh.as_ref().map(|_| {
+        Map::from_vec(
+            map.iter()
+                .map(|cell| cell.elevation.max(sea_level))
+                .collect(),
+            map.width(),
+            map.height(),
+        )
+    });
+
+    // previewing without color shows the elevation, which is lost
+    // once the map is colored
+    let preview_ascii = args.preview_ascii || !supports_truecolor();
+    let preview_elevation = (args.preview && preview_ascii).then(|| {
+        Map::from_vec(
+            map.iter().map(|cell| cell.elevation).collect(),
+            map.width(),
+            map.height(),
+        )
+    });
+
+    let lookup_table = args
+        .color_lookup
+        .map(|resolution| evaluator.lookup_table(resolution));
+
+    let map = map.and_then_with_coordinates(|cell, x, y| {
         let (elevation, moisture) = (cell.elevation, cell.moisture);
 
-        if elevation < sea_level {
-            let normalized_elevation = normalize(elevation, 0.0, sea_level);
+        if elevation < sea_level && args.transparent_ocean {
+            Rgba([0, 0, 0, 0])
+        } else if elevation < sea_level {
+            let depth = match &distance_to_land {
+                Some((distance, max_distance)) => {
+                    1.0 - normalize(*distance.get(x, y), 0.0, *max_distance).min(1.0)
+                }
+                None => normalize(elevation, 0.0, sea_level),
+            };
 
-            ocean.lock().expect("failed to acquire lock")(normalized_elevation)
+            ocean(depth)
+        } else if let Some(lookup_table) = &lookup_table {
+            lookup_table.evaluate(elevation, moisture)
         } else {
             evaluator.evaluate(elevation, moisture)
         }
     });
-    let image = map.extract(pixel_map_to_image);
 
-    image.save("image.png").expect("failed to save image");
+    // colors are taken before shading so that lighting the mesh is
+    // left to whatever renders it
+    if let (Some(path), Some(surface)) = (&args.mesh, &mesh_surface) {
+        save_mesh(&args, path, surface, &map)?;
+    }
+
+    let map = match (&hillshade, &shade) {
+        (Some(hillshade), Some(shade)) if shade_map => {
+            map.and_then_with_coordinates(|color, x, y| hillshade.apply(*color, *shade.get(x, y)))
+        }
+        _ => map,
+    };
+    let map = match &contours {
+        Some((generator, contours)) => generator.draw(map, contours),
+        None => map,
+    };
+
+    if args.preview {
+        let columns = args.preview_width.unwrap_or_else(terminal_width);
+
+        match &preview_elevation {
+            Some(elevation) => print!("{}", render_ascii(elevation, columns, sea_level)),
+            None => {
+                let pixels = Map::from_vec(
+                    map.iter().map(|pixel| pixel.to_rgb()).collect(),
+                    map.width(),
+                    map.height(),
+                );
+
+                print!("{}", render_truecolor(&pixels, columns));
+            }
+        }
+    }
+
+    // a bundle holds the map in place of the output
+    let output = match &args.bundle {
+        Some(bundle) if in_bundle(BundleLayer::Map) => {
+            manifest = manifest.map(|manifest| manifest.file("map", "map.png", "the colored map"));
+
+            Some(Path::new(bundle).join("map.png"))
+        }
+        Some(_) => None,
+        None => Some(PathBuf::from(&args.output)),
+    };
+
+    if let Some(output) = output {
+        let image = if args.alpha || args.transparent_ocean {
+            DynamicImage::ImageRgba8(map.extract(pixel_map_to_rgba_image))
+        } else {
+            let map = map.and_then(|pixel| pixel.to_rgb());
+
+            DynamicImage::ImageRgb8(map.extract(pixel_map_to_image))
+        };
+        let is_png = output
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+
+        // embed how the map was generated so that it can be generated
+        // again from the image alone
+        if is_png {
+            let metadata = MapMetadata {
+                version: String::from(env!("CARGO_PKG_VERSION")),
+                seed: args.seed,
+                width: args.width,
+                height: args.height,
+                sea_level,
+                config: config.to_yaml().context("failed to serialize config")?,
+                config_hash: Some(config_hash),
+                arguments: std::env::args().skip(1).collect(),
+            };
+
+            metadata
+                .save_png(&image, output)
+                .context("failed to save image")?;
+        } else {
+            image.save(output).context("failed to save image")?;
+        }
+    }
+    if let (Some(bundle), Some(manifest)) = (&args.bundle, manifest) {
+        std::fs::write(
+            Path::new(bundle).join("manifest.json"),
+            manifest.build().to_json(),
+        )
+        .context("failed to save bundle manifest")?;
+    }
 
     Ok(())
 }
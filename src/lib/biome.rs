@@ -0,0 +1,275 @@
+//! This module provides a [`BiomeClassifier`] for sorting the cells
+//! of a map into discrete biomes.
+//!
+//! Classifying cells separately from coloring them keeps a record
+//! of which biome every cell belongs to, which can then be used to
+//! compute statistics about the biomes or to export masks of them.
+//!
+//! # Examples
+//!
+//! ```
+//! use ficture::biome::{biome_areas, BiomeId};
+//! use ficture::map::{Map, MapMonad};
+//!
+//! let map = Map::return_single(BiomeId(1), 10, 10);
+//! let areas = biome_areas(&map);
+//!
+//! assert_eq!(areas[&BiomeId(1)], 100);
+//! ```
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{cell::Cell, config::Biomes, map::Map};
+
+/// The identifier of a biome. Identifiers are assigned in the
+/// config file so that they stay the same between runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BiomeId(pub u16);
+
+impl BiomeId {
+    /// The identifier of cells that don't belong to any biome,
+    /// such as cells below sea level.
+    pub const NONE: BiomeId = BiomeId(0);
+}
+
+/// Information about a single biome in a biome map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Biome {
+    /// The identifier of the biome.
+    pub id: BiomeId,
+    /// The name of the biome.
+    pub name: String,
+    /// The normalized range of elevation covered by the biome.
+    pub elevation: (f64, f64),
+    /// The normalized range of moisture covered by the biome.
+    pub moisture: (f64, f64),
+}
+
+/// A structure containing the upper bound of a single
+/// elevation range and the moisture ranges within it.
+struct ElevationRange {
+    elevation: f64,
+    moisture_ranges: Vec<MoistureRange>,
+}
+
+/// A structure containing the upper bound of a single
+/// moisture range and the biome it belongs to.
+struct MoistureRange {
+    moisture: f64,
+    biome: usize,
+}
+
+/// A structure for classifying cells into biomes based on their
+/// elevation and moisture.
+pub struct BiomeClassifier {
+    elevation_ranges: Vec<ElevationRange>,
+    biomes: Vec<Biome>,
+    indices: HashMap<BiomeId, usize>,
+}
+
+impl BiomeClassifier {
+    /// Creates a [`BiomeClassifier`] from a biome map loaded from
+    /// a config file.
+    pub(crate) fn from_biomes(biomes: &Biomes) -> Self {
+        let total_elevation = biomes.total_elevation();
+        let ids = biomes.biome_ids();
+        let mut ids = ids.iter();
+        let mut elevation_ranges = vec![];
+        let mut classified_biomes = vec![];
+        let mut cumulative_elevation = 0.0;
+
+        for elevation_level in &biomes.elevation_levels {
+            let total_moisture = elevation_level.total_moisture();
+            let lower_elevation = cumulative_elevation / total_elevation;
+            let mut moisture_ranges = vec![];
+            let mut cumulative_moisture = 0.0;
+
+            cumulative_elevation += elevation_level.elevation;
+
+            for moisture_level in &elevation_level.moisture_levels {
+                let id = *ids.next().expect("every moisture level to have an id");
+                let lower_moisture = cumulative_moisture / total_moisture;
+
+                cumulative_moisture += moisture_level.moisture;
+                moisture_ranges.push(MoistureRange {
+                    moisture: cumulative_moisture / total_moisture,
+                    biome: classified_biomes.len(),
+                });
+                classified_biomes.push(Biome {
+                    id,
+                    name: moisture_level
+                        .name
+                        .clone()
+                        .unwrap_or_else(|| format!("biome_{}", id.0)),
+                    elevation: (lower_elevation, cumulative_elevation / total_elevation),
+                    moisture: (lower_moisture, cumulative_moisture / total_moisture),
+                });
+            }
+
+            elevation_ranges.push(ElevationRange {
+                elevation: cumulative_elevation / total_elevation,
+                moisture_ranges,
+            });
+        }
+
+        let indices = classified_biomes
+            .iter()
+            .enumerate()
+            .map(|(index, biome)| (biome.id, index))
+            .collect();
+
+        Self {
+            elevation_ranges,
+            biomes: classified_biomes,
+            indices,
+        }
+    }
+
+    /// Gets the index of the biome that a cell with the given
+    /// elevation and moisture belongs to. The ranges are found by
+    /// binary searching their cumulative upper bounds.
+    pub(crate) fn classify_index(&self, elevation: f64, moisture: f64) -> Option<usize> {
+        let elevation_index = self
+            .elevation_ranges
+            .partition_point(|range| range.elevation < elevation);
+        let moisture_ranges = &self.elevation_ranges.get(elevation_index)?.moisture_ranges;
+        let moisture_index = moisture_ranges.partition_point(|range| range.moisture < moisture);
+
+        moisture_ranges.get(moisture_index).map(|range| range.biome)
+    }
+
+    /// Gets the biome that a cell with the given elevation and
+    /// moisture belongs to. Returns [`BiomeId::NONE`] if the values
+    /// fall outside of the biome map.
+    pub fn classify(&self, elevation: f64, moisture: f64) -> BiomeId {
+        self.classify_index(elevation, moisture)
+            .map(|index| self.biomes[index].id)
+            .unwrap_or(BiomeId::NONE)
+    }
+
+    /// Classifies every cell of a map into a biome. Cells below
+    /// `sea_level` are left as [`BiomeId::NONE`], since they are
+    /// colored as the ocean instead of by the biome map.
+    pub fn classify_map(&self, map: &Map<Cell>, sea_level: f64) -> Map<BiomeId> {
+        Map::from_vec(
+            map.iter()
+                .map(|cell| {
+                    if cell.elevation < sea_level {
+                        BiomeId::NONE
+                    } else {
+                        self.classify(cell.elevation, cell.moisture)
+                    }
+                })
+                .collect(),
+            map.width(),
+            map.height(),
+        )
+    }
+
+    /// Gets every biome in the biome map, in the order that they
+    /// are declared in the config file.
+    pub fn biomes(&self) -> &[Biome] {
+        &self.biomes
+    }
+
+    /// Gets the biome with the given identifier.
+    pub fn biome(&self, id: BiomeId) -> Option<&Biome> {
+        self.indices.get(&id).map(|index| &self.biomes[*index])
+    }
+
+    /// Gets the index of the biome with the given identifier.
+    pub(crate) fn index_of(&self, id: BiomeId) -> Option<usize> {
+        self.indices.get(&id).copied()
+    }
+}
+
+/// Counts the number of cells that belong to each biome.
+pub fn biome_areas(map: &Map<BiomeId>) -> BTreeMap<BiomeId, usize> {
+    let mut areas = BTreeMap::new();
+
+    for id in map.iter() {
+        *areas.entry(*id).or_insert(0) += 1;
+    }
+
+    areas
+}
+
+/// Creates a mask of the cells that belong to a single biome.
+pub fn biome_mask(map: &Map<BiomeId>, id: BiomeId) -> Map<bool> {
+    Map::from_vec(
+        map.iter().map(|cell| *cell == id).collect(),
+        map.width(),
+        map.height(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn biomes() -> Biomes {
+        serde_yaml::from_str(
+            r##"
+            elevation_levels:
+              - elevation: 0.5
+                moisture_levels:
+                  - name: desert
+                    id: 7
+                    moisture: 0.5
+                    gradient: ["#000000"]
+                  - moisture: 0.5
+                    gradient: ["#ffffff"]
+              - elevation: 0.5
+                moisture_levels:
+                  - name: snow
+                    moisture: 1.0
+                    gradient: ["#ffffff"]
+            "##,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_classify() {
+        let classifier = BiomeClassifier::from_biomes(&biomes());
+
+        assert_eq!(classifier.classify(0.2, 0.2), BiomeId(7));
+        assert_eq!(classifier.classify(0.2, 0.8), BiomeId(2));
+        assert_eq!(classifier.classify(0.8, 0.2), BiomeId(3));
+        assert_eq!(classifier.classify(1.5, 0.2), BiomeId::NONE);
+        assert_eq!(classifier.biome(BiomeId(2)).unwrap().name, "biome_2");
+        assert_eq!(classifier.biome(BiomeId(3)).unwrap().elevation, (0.5, 1.0));
+    }
+
+    #[test]
+    fn test_classify_map_leaves_ocean_unclassified() {
+        let classifier = BiomeClassifier::from_biomes(&biomes());
+        let cell = |elevation, moisture| Cell {
+            elevation,
+            moisture,
+        };
+        let map = Map::from_vec(vec![cell(0.01, 0.2), cell(0.2, 0.2), cell(0.8, 0.2)], 3, 1);
+        let biomes = classifier.classify_map(&map, 0.05);
+
+        assert_eq!(*biomes, vec![BiomeId::NONE, BiomeId(7), BiomeId(3)]);
+    }
+
+    #[test]
+    fn test_duplicate_biome_ids_are_invalid() {
+        let mut biomes = biomes();
+
+        assert!(biomes.validate().is_ok());
+
+        biomes.elevation_levels[1].moisture_levels[0].id = Some(7);
+
+        assert!(biomes.validate().is_err());
+    }
+
+    #[test]
+    fn test_biome_mask() {
+        let map = Map::from_vec(vec![BiomeId(1), BiomeId(2), BiomeId(1)], 3, 1);
+        let mask = biome_mask(&map, BiomeId(1));
+
+        assert_eq!(*mask, vec![true, false, true]);
+        assert_eq!(biome_areas(&map)[&BiomeId(1)], 2);
+    }
+}
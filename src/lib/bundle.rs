@@ -0,0 +1,273 @@
+//! This module provides a [`Manifest`] describing a bundle of files
+//! saved from a single run, such as the colored map alongside its
+//! elevation, moisture and biomes.
+//!
+//! The manifest records everything needed to tell where the files
+//! came from and to generate them again: the seed of every generator,
+//! the dimensions of the map, and the path and hash of the config
+//! file. It also lists
+//! every file in the bundle and the identifiers of the biomes, which
+//! are needed to read a map of biome identifiers.
+//!
+//! # Examples
+//!
+//! ```
+//! use ficture::bundle::{hash_bytes, ManifestBuilder};
+//!
+//! let manifest = ManifestBuilder::new(1920, 1080)
+//!     .seed(Some(42))
+//!     .generator_seed("noise_generators.elevation_noise", 7)
+//!     .config("config/config.yaml", hash_bytes(b"noise_generators: {}"))
+//!     .file("map", "map.png", "the colored map")
+//!     .build();
+//!
+//! assert!(manifest.to_json().contains("\"seed\": 42"));
+//! assert!(manifest
+//!     .to_json()
+//!     .contains("\"noise_generators.elevation_noise\": \"7\""));
+//! ```
+use std::collections::BTreeMap;
+
+use crate::biome::BiomeId;
+
+/// Hashes bytes with the 64 bit FNV-1a hash, which is stable between
+/// runs, platforms and versions of Rust.
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Quotes a string for JSON, escaping any characters that need it.
+fn json_string(value: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// A single file in a bundle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestFile {
+    /// The name of the layer saved in the file.
+    pub layer: String,
+    /// The path of the file, relative to the bundle.
+    pub path: String,
+    /// A description of what the file contains.
+    pub description: String,
+}
+
+/// A description of a bundle of files saved from a single run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    /// The seed that replaced the seeds of the config file, if any.
+    pub seed: Option<u32>,
+    /// The seed used by every generator, keyed by where the
+    /// generator is found in the config file.
+    pub seeds: BTreeMap<String, u64>,
+    /// The width of the map in cells.
+    pub width: usize,
+    /// The height of the map in cells.
+    pub height: usize,
+    /// The path of the config file.
+    pub config: String,
+    /// The hash of the contents of the config file.
+    pub config_hash: u64,
+    /// The elevation of the sea level.
+    pub sea_level: f64,
+    /// The identifier and name of every biome.
+    pub biomes: Vec<(BiomeId, String)>,
+    /// Every file in the bundle.
+    pub files: Vec<ManifestFile>,
+}
+
+impl Manifest {
+    /// Converts the manifest into JSON.
+    pub fn to_json(&self) -> String {
+        let seed = match self.seed {
+            Some(seed) => format!("  \"seed\": {seed},\n"),
+            None => String::new(),
+        };
+        let seeds = self
+            .seeds
+            .iter()
+            .map(|(generator, seed)| format!("    {}: \"{seed}\"", json_string(generator)))
+            .collect::<Vec<_>>()
+            .join(",\n");
+        let biomes = self
+            .biomes
+            .iter()
+            .map(|(id, name)| format!("    {{\"id\": {}, \"name\": {}}}", id.0, json_string(name)))
+            .collect::<Vec<_>>()
+            .join(",\n");
+        let files = self
+            .files
+            .iter()
+            .map(|file| {
+                format!(
+                    "    {{\"layer\": {}, \"path\": {}, \"description\": {}}}",
+                    json_string(&file.layer),
+                    json_string(&file.path),
+                    json_string(&file.description)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",\n");
+
+        // the hash and seeds are written as strings since JSON numbers
+        // can't hold every 64 bit integer
+        format!(
+            concat!(
+                "{{\n",
+                "{}",
+                "  \"seeds\": {{\n{}\n  }},\n",
+                "  \"width\": {},\n",
+                "  \"height\": {},\n",
+                "  \"config\": {},\n",
+                "  \"config_hash\": \"{:016x}\",\n",
+                "  \"sea_level\": {},\n",
+                "  \"biomes\": [\n{}\n  ],\n",
+                "  \"files\": [\n{}\n  ]\n",
+                "}}\n"
+            ),
+            seed,
+            seeds,
+            self.width,
+            self.height,
+            json_string(&self.config),
+            self.config_hash,
+            self.sea_level,
+            biomes,
+            files
+        )
+    }
+}
+
+/// A builder for the [`Manifest`].
+pub struct ManifestBuilder {
+    seed: Option<u32>,
+    seeds: BTreeMap<String, u64>,
+    width: usize,
+    height: usize,
+    config: String,
+    config_hash: u64,
+    sea_level: f64,
+    biomes: Vec<(BiomeId, String)>,
+    files: Vec<ManifestFile>,
+}
+
+impl ManifestBuilder {
+    /// Creates the [`ManifestBuilder`] for a map with the given
+    /// dimensions.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            seed: None,
+            seeds: BTreeMap::new(),
+            width,
+            height,
+            config: String::new(),
+            config_hash: 0,
+            sea_level: 0.0,
+            biomes: vec![],
+            files: vec![],
+        }
+    }
+
+    /// Sets the seed that replaced the seeds of the config file.
+    pub fn seed(mut self, seed: Option<u32>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Adds the seed used by a generator, named by where it is found
+    /// in the config file.
+    pub fn generator_seed(mut self, generator: impl Into<String>, seed: u64) -> Self {
+        self.seeds.insert(generator.into(), seed);
+        self
+    }
+
+    /// Sets the path and hash of the config file.
+    pub fn config(mut self, config: impl Into<String>, config_hash: u64) -> Self {
+        self.config = config.into();
+        self.config_hash = config_hash;
+        self
+    }
+
+    /// Sets the elevation of the sea level.
+    pub fn sea_level(mut self, sea_level: f64) -> Self {
+        self.sea_level = sea_level;
+        self
+    }
+
+    /// Adds a biome to the list of biomes.
+    pub fn biome(mut self, id: BiomeId, name: impl Into<String>) -> Self {
+        self.biomes.push((id, name.into()));
+        self
+    }
+
+    /// Adds a file to the bundle.
+    pub fn file(
+        mut self,
+        layer: impl Into<String>,
+        path: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        self.files.push(ManifestFile {
+            layer: layer.into(),
+            path: path.into(),
+            description: description.into(),
+        });
+        self
+    }
+
+    /// Construct the [`Manifest`] based on the
+    /// defined attributes.
+    pub fn build(self) -> Manifest {
+        Manifest {
+            seed: self.seed,
+            seeds: self.seeds,
+            width: self.width,
+            height: self.height,
+            config: self.config,
+            config_hash: self.config_hash,
+            sea_level: self.sea_level,
+            biomes: self.biomes,
+            files: self.files,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hash_is_stable() {
+        assert_eq!(hash_bytes(b""), 0xcbf29ce484222325);
+        assert_eq!(hash_bytes(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn test_manifest_escapes_strings() {
+        let manifest = ManifestBuilder::new(4, 2)
+            .config("C:\\maps\\\"config\".yaml", 255)
+            .biome(BiomeId(3), "snow")
+            .build();
+        let json = manifest.to_json();
+
+        assert!(json.contains(r#""config": "C:\\maps\\\"config\".yaml""#));
+        assert!(json.contains(r#""config_hash": "00000000000000ff""#));
+        assert!(json.contains(r#"{"id": 3, "name": "snow"}"#));
+        assert!(!json.contains(r#""seed""#));
+    }
+}
@@ -1,5 +1,7 @@
 //! This module provides a [`Cell`] representing a single point
-//! on a 2D world map.
+//! on a 2D world map, along with a [`NamedCell`] for cells
+//! with an arbitrary set of named channels.
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Cell {
@@ -15,3 +17,54 @@ pub struct Cell {
 // itself, so there should be no issue making Cell Send and Sync.
 unsafe impl Send for Cell {}
 unsafe impl Sync for Cell {}
+
+/// A trait describing a cell whose values can be looked up
+/// by name. This allows biome maps to be evaluated over any
+/// set of factors rather than only elevation and moisture.
+pub trait Channels {
+    /// Gets the value of the channel named `name`, if the
+    /// cell has one.
+    fn channel(&self, name: &str) -> Option<f64>;
+}
+
+impl Channels for Cell {
+    fn channel(&self, name: &str) -> Option<f64> {
+        match name {
+            "elevation" => Some(self.elevation),
+            "moisture" => Some(self.moisture),
+            _ => None,
+        }
+    }
+}
+
+/// A cell with any number of named channels, such as temperature,
+/// moisture and elevation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NamedCell {
+    channels: HashMap<String, f64>,
+}
+
+impl NamedCell {
+    /// Creates a [`NamedCell`] without any channels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the value of a channel, returning the cell so that
+    /// channels can be chained.
+    pub fn with(mut self, name: impl Into<String>, value: f64) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    /// Sets the value of a channel.
+    pub fn set(&mut self, name: impl Into<String>, value: f64) {
+        self.channels.insert(name.into(), value);
+    }
+}
+
+impl Channels for NamedCell {
+    fn channel(&self, name: &str) -> Option<f64> {
+        self.channels.get(name).copied()
+    }
+}
@@ -0,0 +1,241 @@
+//! This module provides a moisture model that simulates humidity
+//! being carried inland from the oceans by prevailing winds.
+//!
+//! Unlike moisture generated from noise, moisture produced by the
+//! [`WindMoistureModel`] depends on the terrain. Air picks up
+//! humidity while it travels over water and loses it as rain while
+//! it travels over land, dropping more of it when it is forced up
+//! the side of a mountain. This leaves the far side of mountain
+//! ranges in a rain shadow.
+//!
+//! # Examples
+//!
+//! ```
+//! use ficture::cell::Cell;
+//! use ficture::climate::WindMoistureModelBuilder;
+//! use ficture::map::{Map, MapMonad};
+//!
+//! let map = Map::return_single(Cell { elevation: 0.0, moisture: 0.0 }, 10, 10);
+//! let map = map.and_then_with_coordinates(|_, x, _| Cell {
+//!     elevation: x as f64 / 10.0,
+//!     moisture: 0.0,
+//! });
+//! let elevation = map.and_then(|cell| cell.elevation);
+//!
+//! let model = WindMoistureModelBuilder::new().build();
+//! let moisture = model.simulate(&elevation, 0.05);
+//! ```
+use rayon::prelude::*;
+
+use crate::{map::Map, utils::wrap};
+
+/// The direction that the prevailing wind blows in a band of
+/// latitude.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wind {
+    /// Wind blowing from the east towards the west.
+    Easterly,
+    /// Wind blowing from the west towards the east.
+    Westerly,
+}
+
+impl Wind {
+    /// Gets the prevailing wind for a latitude given in degrees. This
+    /// follows the trade winds, westerlies and polar easterlies found
+    /// on Earth.
+    pub fn at_latitude(latitude: f64) -> Self {
+        let latitude = latitude.abs();
+
+        if (30.0..60.0).contains(&latitude) {
+            Wind::Westerly
+        } else {
+            Wind::Easterly
+        }
+    }
+
+    /// Gets the change in x that a parcel of air makes with each
+    /// step it takes in this wind.
+    fn step(&self) -> isize {
+        match self {
+            Wind::Easterly => -1,
+            Wind::Westerly => 1,
+        }
+    }
+}
+
+/// A moisture model that advects humidity from the oceans along
+/// prevailing wind bands and drops it as precipitation over land.
+pub struct WindMoistureModel {
+    evaporation: f64,
+    precipitation: f64,
+    orographic: f64,
+}
+
+impl WindMoistureModel {
+    /// Simulates the moisture for every cell of a map given the
+    /// normalized elevation of each cell and the sea level. Every
+    /// row of the map is simulated concurrently. The resulting
+    /// moisture is normalized from 0-1 so that it can be used in
+    /// place of the `moisture` field of a [`Cell`](crate::cell::Cell).
+    pub fn simulate(&self, elevation: &Map<f64>, sea_level: f64) -> Map<f64> {
+        let (width, height) = (elevation.width(), elevation.height());
+        let rows: Vec<Vec<f64>> = (0..height)
+            .into_par_iter()
+            .map(|y| self.simulate_row(elevation, sea_level, y))
+            .collect();
+
+        // rows are simulated independently, so smooth them into one
+        // another to avoid streaks along the wind direction
+        let mut moisture = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            let above = &rows[y.saturating_sub(1)];
+            let below = &rows[(y + 1).min(height - 1)];
+
+            for x in 0..width {
+                moisture.push(0.25 * above[x] + 0.5 * rows[y][x] + 0.25 * below[x]);
+            }
+        }
+
+        let max_moisture = moisture.iter().cloned().fold(0.0, f64::max);
+
+        if max_moisture > 0.0 {
+            moisture.iter_mut().for_each(|value| *value /= max_moisture);
+        }
+
+        Map::from_vec(moisture, width, height)
+    }
+
+    /// Carries a parcel of air across a single row of the map. The
+    /// parcel makes two trips around the world so that the humidity
+    /// it carries has settled by the time values are recorded.
+    fn simulate_row(&self, elevation: &Map<f64>, sea_level: f64, y: usize) -> Vec<f64> {
+        let width = elevation.width();
+        let latitude = 90.0 - (y as f64 + 0.5) / elevation.height() as f64 * 180.0;
+        let step = Wind::at_latitude(latitude).step();
+        let mut moisture = vec![0.0; width];
+        let mut humidity: f64 = 0.0;
+        let mut x = 0;
+        let mut previous_elevation = *elevation.get(0, y);
+
+        for lap in 0..2 {
+            for _ in 0..width {
+                let current_elevation = *elevation.get(x, y);
+
+                if current_elevation < sea_level {
+                    humidity += self.evaporation * (1.0 - humidity);
+                } else {
+                    let rise = (current_elevation - previous_elevation.max(sea_level)).max(0.0);
+                    let rain =
+                        (humidity * (self.precipitation + self.orographic * rise)).min(humidity);
+
+                    humidity -= rain;
+                }
+
+                if lap == 1 {
+                    moisture[x] = humidity;
+                }
+
+                previous_elevation = current_elevation;
+                x = wrap(x as isize + step, width);
+            }
+        }
+
+        moisture
+    }
+}
+
+/// A builder for the [`WindMoistureModel`].
+pub struct WindMoistureModelBuilder {
+    evaporation: f64,
+    precipitation: f64,
+    orographic: f64,
+}
+
+impl WindMoistureModelBuilder {
+    /// Creates the [`WindMoistureModelBuilder`].
+    pub fn new() -> Self {
+        Self {
+            evaporation: 0.1,
+            precipitation: 0.01,
+            orographic: 2.0,
+        }
+    }
+
+    /// Sets the fraction of the remaining humidity capacity that
+    /// air picks up each time it passes over water.
+    pub fn evaporation(mut self, evaporation: f64) -> Self {
+        self.evaporation = evaporation;
+        self
+    }
+
+    /// Sets the fraction of the carried humidity that falls as
+    /// rain each time air passes over flat land.
+    pub fn precipitation(mut self, precipitation: f64) -> Self {
+        self.precipitation = precipitation;
+        self
+    }
+
+    /// Sets how much additional rain falls as air is forced up
+    /// rising terrain. A higher value leaves drier rain shadows
+    /// behind mountain ranges.
+    pub fn orographic(mut self, orographic: f64) -> Self {
+        self.orographic = orographic;
+        self
+    }
+
+    /// Construct the [`WindMoistureModel`] based on the
+    /// defined attributes.
+    pub fn build(self) -> WindMoistureModel {
+        WindMoistureModel {
+            evaporation: self.evaporation,
+            precipitation: self.precipitation,
+            orographic: self.orographic,
+        }
+    }
+}
+
+impl Default for WindMoistureModelBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_wind_bands() {
+        assert_eq!(Wind::at_latitude(10.0), Wind::Easterly);
+        assert_eq!(Wind::at_latitude(-45.0), Wind::Westerly);
+        assert_eq!(Wind::at_latitude(75.0), Wind::Easterly);
+    }
+
+    #[test]
+    fn test_mountains_cast_rain_shadow() {
+        let (width, height) = (40, 20);
+        // ocean to the west and land to the east, in the westerlies so
+        // the wind blows in from the ocean
+        let terrain = |ridge: f64| {
+            Map::from_vec(
+                (0..width * height)
+                    .map(|index| match index % width {
+                        0..=9 => 0.0,
+                        18..=22 => ridge,
+                        _ => 0.1,
+                    })
+                    .collect(),
+                width,
+                height,
+            )
+        };
+        let model = WindMoistureModelBuilder::new().build();
+        let flat = model.simulate(&terrain(0.1), 0.05);
+        let mountains = model.simulate(&terrain(0.6), 0.05);
+        let y = 5;
+
+        assert!(*mountains.get(25, y) < *flat.get(25, y));
+        assert!(mountains.iter().all(|value| (0.0..=1.0).contains(value)));
+    }
+}
@@ -2,13 +2,18 @@
 //! certain points in a gradient. The gradients represent
 //! the colors used for different biomes on the map and
 //! the final colors are chosen based on elevation.
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-use colorgrad::{Color, CustomGradient, Gradient};
-use image::Rgb;
+use colorgrad::{BlendMode, Color, CustomGradient, Gradient, Interpolation};
+use image::Rgba;
+use rayon::prelude::*;
 use thiserror::Error;
 
-use crate::{config::Biomes, utils::normalize};
+use crate::{
+    biome::{BiomeClassifier, BiomeId},
+    config::{Biomes, GradientBlendSpace, GradientInterpolation, GradientSpec},
+    utils::normalize,
+};
 
 /// The error type for color errors.
 #[derive(Error, Debug)]
@@ -21,52 +26,92 @@ pub enum ColorError {
 pub type ColorResult<T> = Result<T, ColorError>;
 
 /// Gets the value at `x` in a gradient and converts it
-/// into an RGB value.
-fn gradient_to_rgb(gradient: &Gradient, x: f64) -> Rgb<u8> {
+/// into an RGBA value.
+fn gradient_to_rgba(gradient: &Gradient, x: f64) -> Rgba<u8> {
     let color = gradient.at(x);
 
-    Rgb([
+    Rgba([
         (color.r * 255.0) as u8,
         (color.g * 255.0) as u8,
         (color.b * 255.0) as u8,
+        (color.a * 255.0) as u8,
     ])
 }
 
-/// A type for a function that can get a color from a gradient.
-pub(crate) type ColorFunc = Arc<Mutex<dyn Fn(f64) -> Rgb<u8> + Send + Sync>>;
+/// Linearly interpolates between the colors `a` and `b`, where a
+/// `t` of 0 gives `a` and a `t` of 1 gives `b`.
+fn lerp_rgba(a: Rgba<u8>, b: Rgba<u8>, t: f64) -> Rgba<u8> {
+    let channel = |i: usize| (a.0[i] as f64 + (b.0[i] as f64 - a.0[i] as f64) * t).round() as u8;
+
+    Rgba([channel(0), channel(1), channel(2), channel(3)])
+}
+
+/// A type for a function that can get a color from a gradient. These
+/// can be shared between threads and called without locking.
+pub type ColorFunc = Arc<dyn Fn(f64) -> Rgba<u8> + Send + Sync>;
 
 /// Converts a gradient into a function that can get a color from that gradient.
-pub(crate) fn get_color_func(gradient: &Vec<String>) -> ColorResult<ColorFunc> {
+pub(crate) fn get_color_func(gradient: &GradientSpec) -> ColorResult<ColorFunc> {
     let mut colors: Vec<Color> = vec![];
 
-    for color in gradient {
+    for color in gradient.colors() {
         colors.push(Color::from_html(color).map_err(|_| ColorError::InvalidGradient)?);
     }
 
-    let gradient = CustomGradient::new()
-        .colors(&colors)
-        .build()
-        .expect("failed to build gradient");
+    // every color needs its own stop, otherwise the gradient can't
+    // be positioned
+    if gradient
+        .stops()
+        .is_some_and(|stops| stops.len() != colors.len())
+    {
+        return Err(ColorError::InvalidGradient);
+    }
 
-    Ok(Arc::new(Mutex::new(move |x| gradient_to_rgb(&gradient, x))))
-}
+    let mut builder = CustomGradient::new();
 
-/// A structure containing information for a single
-/// elevation range, or the x axis of a biome map.
-struct ElevationRange {
-    /// The value of the primary factor for the evaluator.
-    elevation: f64,
-    /// The moisture gradients associated with this range.
-    moisture_gradients: Vec<MoistureGradient>,
-}
+    if gradient.interpolation() == GradientInterpolation::Step {
+        // each color is repeated at its own stop and the next stop,
+        // which leaves no space between them to interpolate over
+        let stops = match gradient.stops() {
+            Some(stops) => stops.to_vec(),
+            None => (0..colors.len())
+                .map(|index| index as f64 / colors.len() as f64)
+                .collect(),
+        };
+        let mut stepped_colors = vec![];
+        let mut positions = vec![];
+
+        for (index, color) in colors.iter().enumerate() {
+            stepped_colors.extend([color.clone(), color.clone()]);
+            positions.extend([stops[index], stops.get(index + 1).copied().unwrap_or(1.0)]);
+        }
+
+        builder.colors(&stepped_colors).domain(&positions);
+    } else {
+        builder
+            .colors(&colors)
+            .interpolation(match gradient.interpolation() {
+                GradientInterpolation::Basis => Interpolation::Basis,
+                GradientInterpolation::CatmullRom => Interpolation::CatmullRom,
+                _ => Interpolation::Linear,
+            })
+            .mode(match gradient.blend_space() {
+                GradientBlendSpace::Rgb => BlendMode::Rgb,
+                GradientBlendSpace::LinearRgb => BlendMode::LinearRgb,
+                GradientBlendSpace::Oklab => BlendMode::Oklab,
+                GradientBlendSpace::Hsv => BlendMode::Hsv,
+            });
+
+        // a single color is the same everywhere, so its stop is
+        // ignored
+        if let (Some(stops), true) = (gradient.stops(), colors.len() > 1) {
+            builder.domain(stops);
+        }
+    }
+
+    let gradient = builder.build().map_err(|_| ColorError::InvalidGradient)?;
 
-/// A structure containing information for a single
-/// moisture range, or the y axis of a biome map.
-struct MoistureGradient {
-    /// The value of the secondary factor for the evaluator.
-    moisture: f64,
-    /// A function pointer for getting the color in this gradient.
-    get_color: ColorFunc,
+    Ok(Arc::new(move |x| gradient_to_rgba(&gradient, x)))
 }
 
 /// A structure for evaluating colors from biome maps. The primary
@@ -74,41 +119,151 @@ struct MoistureGradient {
 /// a cell's elevation and moisture levels. Despite using the terms
 /// "elevation" and "moisture", this can be used with any two
 /// factors to get a color.
+///
+/// Colors are evaluated in two steps. Cells are first classified
+/// into a biome by a [`BiomeClassifier`], and then colored using the
+/// gradient of that biome.
 pub struct ColorEvaluator {
-    /// The ranges for the "elevation" factor of the
-    /// color evaluator.
-    elevation_ranges: Vec<ElevationRange>,
+    /// The classifier for the biomes of the biome map.
+    classifier: BiomeClassifier,
+    /// The functions for getting the colors of each biome, in
+    /// the same order as the biomes of the classifier.
+    colors: Vec<ColorFunc>,
+    /// The range of elevation that the gradient of each biome is
+    /// spread over, in the same order as the biomes of the classifier.
+    gradient_spans: Vec<(f64, f64)>,
+    /// The width of the band around each threshold over which
+    /// the colors of neighboring biomes are blended.
+    blend_width: f64,
 }
 
 impl ColorEvaluator {
     /// Creates a [`ColorEvaluator`] from a biome map loaded from
     /// a config file.
     pub(crate) fn from_biomes(biomes: &Biomes) -> ColorResult<Self> {
+        let classifier = BiomeClassifier::from_biomes(biomes);
         let total_elevation = biomes.total_elevation();
-        let mut elevation_ranges: Vec<ElevationRange> = vec![];
+        let mut colors: Vec<ColorFunc> = vec![];
+        let mut gradient_spans = vec![];
         let mut cumulative_elevation = 0.0;
+        let mut gradient_start = 0.0;
 
         for elevation_level in &biomes.elevation_levels {
-            let total_moisture = elevation_level.total_moisture();
-            let mut moisture_gradients: Vec<MoistureGradient> = vec![];
-            let mut cumulative_moisture = 0.0;
+            cumulative_elevation += elevation_level.elevation;
+
+            let upper_elevation = cumulative_elevation / total_elevation;
 
             for moisture_level in &elevation_level.moisture_levels {
-                cumulative_moisture += moisture_level.moisture;
-                moisture_gradients.push(MoistureGradient {
-                    moisture: cumulative_moisture / total_moisture,
-                    get_color: get_color_func(&moisture_level.gradient)?,
-                });
+                colors.push(get_color_func(&moisture_level.gradient)?);
+                gradient_spans.push((gradient_start, gradient_start + upper_elevation));
             }
 
-            cumulative_elevation += elevation_level.elevation;
-            elevation_ranges.push(ElevationRange {
-                elevation: cumulative_elevation / total_elevation,
-                moisture_gradients,
-            });
+            // gradients keep the positions that they have always had,
+            // starting where the gradient of the level below ends and
+            // spanning as far as the upper bound of their own level
+            gradient_start += upper_elevation;
+        }
+
+        Ok(Self {
+            classifier,
+            colors,
+            gradient_spans,
+            blend_width: biomes.blend_width,
+        })
+    }
+
+    /// Gets the classifier for the biomes that this evaluator
+    /// colors.
+    pub fn classifier(&self) -> &BiomeClassifier {
+        &self.classifier
+    }
+
+    /// Gets the color of a cell in the given biome. The position
+    /// within the biome's gradient is determined by where the
+    /// elevation falls within the span of elevation that the gradient
+    /// is spread over. Cells that don't belong to a biome are
+    /// transparent.
+    pub fn color(&self, biome: BiomeId, elevation: f64) -> Rgba<u8> {
+        match self.classifier.index_of(biome) {
+            Some(index) => self.color_at(index, elevation),
+            None => Rgba([0, 0, 0, 0]),
+        }
+    }
+
+    /// Gets the color at a position from 0 to 1 along the gradient of
+    /// the given biome. Biomes that aren't in the biome map are
+    /// transparent.
+    pub fn gradient_color(&self, biome: BiomeId, position: f64) -> Rgba<u8> {
+        match self.classifier.index_of(biome) {
+            Some(index) => (self.colors[index])(position.clamp(0.0, 1.0)),
+            None => Rgba([0, 0, 0, 0]),
+        }
+    }
+
+    /// Gets the color of a cell in the biome at `index`.
+    fn color_at(&self, index: usize, elevation: f64) -> Rgba<u8> {
+        let (lower, upper) = self.gradient_spans[index];
+        let get_color = &self.colors[index];
+
+        get_color(normalize(elevation, lower, upper).clamp(0.0, 1.0))
+    }
+
+    /// Gets the color of a cell in the biome at `index`, blended with
+    /// the biomes on the other side of any moisture threshold that the
+    /// cell is close to.
+    fn blend_moisture(&self, index: usize, elevation: f64, moisture: f64) -> Rgba<u8> {
+        let biome = &self.classifier.biomes()[index];
+        let color = self.color_at(index, elevation);
+        let half_width = self.blend_width / 2.0;
+        let (lower, upper) = biome.moisture;
+        // classify neighbors from the middle of this biome's elevation
+        // range so that they are within the same elevation range
+        let middle_elevation = (biome.elevation.0 + biome.elevation.1) / 2.0;
+
+        let (neighbor_moisture, distance) = if moisture - lower < half_width {
+            (lower - f64::EPSILON, moisture - lower)
+        } else if upper - moisture < half_width {
+            (upper + f64::EPSILON, upper - moisture)
+        } else {
+            return color;
+        };
+
+        match self
+            .classifier
+            .classify_index(middle_elevation, neighbor_moisture)
+        {
+            Some(neighbor) if neighbor != index => {
+                let neighbor_color = self.color_at(neighbor, elevation);
+
+                lerp_rgba(neighbor_color, color, 0.5 + 0.5 * distance / half_width)
+            }
+            _ => color,
         }
+    }
+
+    /// Gets a color from a biome map, blending the colors of
+    /// neighboring biomes near the thresholds between them.
+    fn blend(&self, index: usize, elevation: f64, moisture: f64) -> Rgba<u8> {
+        let color = self.blend_moisture(index, elevation, moisture);
+        let half_width = self.blend_width / 2.0;
+        let (lower, upper) = self.classifier.biomes()[index].elevation;
 
-        Ok(Self { elevation_ranges })
+        let (neighbor_elevation, distance) = if elevation - lower < half_width {
+            (lower - f64::EPSILON, elevation - lower)
+        } else if upper - elevation < half_width {
+            (upper + f64::EPSILON, upper - elevation)
+        } else {
+            return color;
+        };
+
+        match self.classifier.classify_index(neighbor_elevation, moisture) {
+            Some(neighbor) if neighbor != index => {
+                let neighbor_color = self.blend_moisture(neighbor, elevation, moisture);
+
+                lerp_rgba(neighbor_color, color, 0.5 + 0.5 * distance / half_width)
+            }
+            _ => color,
+        }
     }
 
     /// Gets a color from a biome map based on two factors. These
@@ -117,33 +272,200 @@ impl ColorEvaluator {
     /// factor of map generation. For example, elevation and moisture
     /// may instead represent temperature and moisture instead in
     /// a particular map.
-    pub fn evaluate(&self, elevation: f64, moisture: f64) -> Rgb<u8> {
-        let mut final_color = Rgb([0, 0, 0]);
-        let mut cumulative_elevation = 0.0;
-
-        for elevation_range in &self.elevation_ranges {
-            if elevation <= elevation_range.elevation {
-                for moisture_gradient in &elevation_range.moisture_gradients {
-                    if moisture <= moisture_gradient.moisture {
-                        let normalized_elevation = normalize(
-                            elevation,
-                            cumulative_elevation,
-                            cumulative_elevation + elevation_range.elevation,
-                        );
-                        let get_color = &moisture_gradient
-                            .get_color
-                            .lock()
-                            .expect("failed to acquire lock");
-
-                        final_color = get_color(normalized_elevation);
-                        break;
-                    }
-                }
-                break;
-            }
-            cumulative_elevation += elevation_range.elevation;
+    ///
+    /// If the biome map has a blend width, colors are interpolated
+    /// between neighboring biomes near the thresholds between them.
+    /// Values outside of the biome map are transparent.
+    pub fn evaluate(&self, elevation: f64, moisture: f64) -> Rgba<u8> {
+        match self.classifier.classify_index(elevation, moisture) {
+            Some(index) if self.blend_width > 0.0 => self.blend(index, elevation, moisture),
+            Some(index) => self.color_at(index, elevation),
+            None => Rgba([0, 0, 0, 0]),
         }
+    }
+
+    /// Precomputes the colors of this evaluator into a lookup table
+    /// with `resolution` steps along each of the two factors.
+    pub fn lookup_table(&self, resolution: usize) -> ColorLookupTable {
+        let resolution = resolution.max(1);
+        let colors = (0..resolution * resolution)
+            .into_par_iter()
+            .map(|index| {
+                let elevation = ((index % resolution) as f64 + 0.5) / resolution as f64;
+                let moisture = ((index / resolution) as f64 + 0.5) / resolution as f64;
+
+                self.evaluate(elevation, moisture)
+            })
+            .collect();
+
+        ColorLookupTable { resolution, colors }
+    }
+}
+
+/// A precomputed table of the colors of a [`ColorEvaluator`] that
+/// gets colors in constant time, at the cost of quantizing both
+/// factors to the resolution of the table.
+pub struct ColorLookupTable {
+    resolution: usize,
+    colors: Vec<Rgba<u8>>,
+}
+
+impl ColorLookupTable {
+    /// Gets the color for the two factors from the table. Factors
+    /// outside of 0-1 are clamped.
+    pub fn evaluate(&self, elevation: f64, moisture: f64) -> Rgba<u8> {
+        let step =
+            |value: f64| ((value * self.resolution as f64) as usize).min(self.resolution - 1);
+
+        self.colors[step(moisture.max(0.0)) * self.resolution + step(elevation.max(0.0))]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn evaluator(blend_width: f64) -> ColorEvaluator {
+        let mut biomes: Biomes = serde_yaml::from_str(
+            r##"
+            elevation_levels:
+              - elevation: 0.5
+                moisture_levels:
+                  - moisture: 0.5
+                    gradient: ["#000000"]
+                  - moisture: 0.5
+                    gradient: ["#0000ff"]
+              - elevation: 0.5
+                moisture_levels:
+                  - moisture: 1.0
+                    gradient: ["#ffffff"]
+            "##,
+        )
+        .unwrap();
+
+        biomes.blend_width = blend_width;
+        ColorEvaluator::from_biomes(&biomes).unwrap()
+    }
+
+    #[test]
+    fn test_gradient_stops() {
+        let gradient: GradientSpec = serde_yaml::from_str(
+            r##"
+            colors: ["#000000", "#ffffff", "#ffffff"]
+            stops: [0.0, 0.1, 1.0]
+            "##,
+        )
+        .unwrap();
+        let color = get_color_func(&gradient).unwrap();
+
+        assert_eq!(color(0.05), Rgba([127, 127, 127, 255]));
+        assert_eq!(color(0.5), Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_step_gradient() {
+        let gradient: GradientSpec = serde_yaml::from_str(
+            r##"
+            colors: ["#000000", "#0000ff"]
+            interpolation: step
+            "##,
+        )
+        .unwrap();
+        let color = get_color_func(&gradient).unwrap();
+
+        assert_eq!(color(0.45), Rgba([0, 0, 0, 255]));
+        assert_eq!(color(0.55), Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn test_step_gradient_with_missing_stops_is_invalid() {
+        let gradient: GradientSpec = serde_yaml::from_str(
+            r##"
+            colors: ["#000000", "#0000ff", "#ffffff"]
+            stops: [0.0, 0.5]
+            interpolation: step
+            "##,
+        )
+        .unwrap();
+
+        assert!(get_color_func(&gradient).is_err());
+    }
+
+    #[test]
+    fn test_gradient_alpha() {
+        let gradient = GradientSpec::Colors(vec![String::from("#ff000080")]);
+        let color = get_color_func(&gradient).unwrap();
+
+        assert_eq!(color(0.5), Rgba([255, 0, 0, 128]));
+        assert_eq!(evaluator(0.0).evaluate(1.5, 0.5), Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_evaluator_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<ColorEvaluator>();
+        assert_send_sync::<ColorLookupTable>();
+    }
+
+    #[test]
+    fn test_lookup_table_matches_evaluator() {
+        let evaluator = evaluator(0.0);
+        let table = evaluator.lookup_table(64);
+
+        assert_eq!(table.evaluate(0.2, 0.2), evaluator.evaluate(0.2, 0.2));
+        assert_eq!(table.evaluate(0.2, 0.8), evaluator.evaluate(0.2, 0.8));
+        assert_eq!(table.evaluate(1.0, 1.0), evaluator.evaluate(1.0, 1.0));
+    }
+
+    #[test]
+    fn test_gradients_keep_their_positions() {
+        let biomes: Biomes = serde_yaml::from_str(
+            r##"
+            elevation_levels:
+              - elevation: 0.5
+                moisture_levels:
+                  - moisture: 1.0
+                    gradient: ["#000000"]
+              - elevation: 0.5
+                moisture_levels:
+                  - moisture: 1.0
+                    gradient: ["#000000", "#ffffff"]
+            "##,
+        )
+        .unwrap();
+        let evaluator = ColorEvaluator::from_biomes(&biomes).unwrap();
+        let gradient = GradientSpec::Colors(vec!["#000000".into(), "#ffffff".into()]);
+        let color = get_color_func(&gradient).unwrap();
+
+        // the upper gradient spans elevations from 0.5 to 1.5
+        assert_eq!(evaluator.evaluate(1.0, 0.5), color(0.5));
+        assert_eq!(evaluator.gradient_color(BiomeId(2), 0.5), color(0.5));
+    }
+
+    #[test]
+    fn test_hard_edges_by_default() {
+        let evaluator = evaluator(0.0);
+
+        assert_eq!(evaluator.evaluate(0.49, 0.2), Rgba([0, 0, 0, 255]));
+        assert_eq!(evaluator.evaluate(0.51, 0.2), Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_blends_near_thresholds() {
+        let evaluator = evaluator(0.2);
+
+        // far from any threshold
+        assert_eq!(evaluator.evaluate(0.1, 0.1), Rgba([0, 0, 0, 255]));
+        // on the elevation threshold
+        assert_eq!(evaluator.evaluate(0.5, 0.1), Rgba([128, 128, 128, 255]));
+        // on the moisture threshold
+        assert_eq!(evaluator.evaluate(0.1, 0.5), Rgba([0, 0, 128, 255]));
+        // approaching the elevation threshold from either side
+        let below = evaluator.evaluate(0.45, 0.1);
+        let above = evaluator.evaluate(0.55, 0.1);
 
-        final_color
+        assert!(below.0[0] > 0 && below.0[0] < 128);
+        assert!(above.0[0] > 128 && above.0[0] < 255);
     }
 }
@@ -1,13 +1,25 @@
 //! This module provides a structure for loading information from config files.
-use std::{collections::HashMap, fs::File};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+};
 
 use colorgrad::Color;
+use image::Rgba;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
+    biome::{BiomeClassifier, BiomeId},
+    bundle::hash_bytes,
+    climate::{WindMoistureModel, WindMoistureModelBuilder},
     color::{get_color_func, ColorEvaluator, ColorFunc},
+    contour::{ContourGenerator, ContourGeneratorBuilder},
+    factor::FactorEvaluator,
+    mask::{BlendMode, ImageMask, MaskLayer, RadialFalloff, SquareFalloff},
     noise::{NoiseGeneratorBuilder, SimpleNoiseGenerator},
+    tectonics::TectonicsGeneratorBuilder,
+    terrain::{self, HillshadeBuilder},
 };
 
 /// The error type returned from validation of the
@@ -16,6 +28,8 @@ use crate::{
 pub enum ConfigError {
     #[error("invalid file (couldn't open the file at {0})")]
     InvalidFilePath(String),
+    #[error("invalid mask image (expected a readable image at {0}, but found {1})")]
+    InvalidMaskImage(String, String),
     #[error("invalid persistence (expected a value greater than 0, but found {0})")]
     InvalidPersistence(f64),
     #[error("invalid lacunarity (expected a value greater than 0, but found {0})")]
@@ -24,8 +38,68 @@ pub enum ConfigError {
     InvalidElevation(f64),
     #[error("invalid moisture (expected a value greater than 0, but found {0})")]
     InvalidMoisture(f64),
+    #[error("invalid evaporation (expected a value from 0-1, but found {0})")]
+    InvalidEvaporation(f64),
+    #[error("invalid precipitation (expected a value from 0-1, but found {0})")]
+    InvalidPrecipitation(f64),
+    #[error("invalid orographic precipitation (expected a value of at least 0, but found {0})")]
+    InvalidOrographic(f64),
+    #[error("invalid radius (expected a value greater than 0, but found {0})")]
+    InvalidRadius(f64),
+    #[error("invalid falloff (expected a value from 0-1, but found {0})")]
+    InvalidFalloff(f64),
+    #[error("invalid strength (expected a value from 0-1, but found {0})")]
+    InvalidStrength(f64),
+    #[error("invalid plates (expected a value greater than 0, but found {0})")]
+    InvalidPlates(usize),
+    #[error("invalid continental fraction (expected a value from 0-1, but found {0})")]
+    InvalidContinentalFraction(f64),
+    #[error("invalid boundary width (expected a value greater than 0, but found {0})")]
+    InvalidBoundaryWidth(f64),
+    #[error("invalid weight (expected a value from 0-1, but found {0})")]
+    InvalidWeight(f64),
+    #[error("invalid altitude (expected a value from 0-90, but found {0})")]
+    InvalidAltitude(f64),
+    #[error("invalid exaggeration (expected a value greater than 0, but found {0})")]
+    InvalidExaggeration(f64),
+    #[error("invalid interval (expected a value greater than 0, but found {0})")]
+    InvalidInterval(f64),
+    #[error("invalid major every (expected a value greater than 0, but found {0})")]
+    InvalidMajorEvery(usize),
     #[error("invalid color (expected a valid html color, but found {0})")]
     InvalidColor(String),
+    #[error("invalid stop (expected a value from 0-1, but found {0})")]
+    InvalidStop(f64),
+    #[error("mismatched stops (expected one stop for each of the {1} colors, but found {0})")]
+    MismatchedStops(usize, usize),
+    #[error("expected the stops of a gradient to be in ascending order")]
+    UnorderedStops,
+    #[error("unknown palette (expected a palette defined in the palettes section, but found {0})")]
+    UnknownPalette(String),
+    #[error("unknown biome (expected a biome defined in the biomes section, but found {0})")]
+    UnknownBiome(String),
+    #[error(
+        "invalid gradient reference (expected {0} to be a gradient rather than another reference)"
+    )]
+    NestedGradientReference(String),
+    #[error("invalid biome id (expected a value greater than 0, but found {0})")]
+    InvalidBiomeId(u16),
+    #[error("duplicate biome id (expected every biome to have a unique id, but found {0} more than once)")]
+    DuplicateBiomeId(u16),
+    #[error("invalid blend width (expected a value of at least 0, but found {0})")]
+    InvalidBlendWidth(f64),
+    #[error("invalid size (expected a value greater than 0, but found {0})")]
+    InvalidSize(f64),
+    #[error("unknown axis (expected one of the declared axes, but found {0})")]
+    UnknownAxis(String),
+    #[error("missing axis (expected the centroid to have a value for {0})")]
+    MissingCentroidAxis(String),
+    #[error("invalid factor range (expected either a split or a biome, but found both)")]
+    InvalidFactorRange,
+    #[error("expected multiple factor ranges to be present, but found none")]
+    MissingFactorRanges,
+    #[error("expected multiple biomes to be present, but found none")]
+    MissingBiomes,
     #[error("expected multiple elevation levels to be present, but found none")]
     MissingElevationLevels,
     #[error("expected multiple moisture levels to be present, but found none")]
@@ -34,6 +108,8 @@ pub enum ConfigError {
     MissingColors,
     #[error("failed to parse config file")]
     FailedToParse,
+    #[error("failed to serialize config")]
+    FailedToSerialize,
 }
 
 /// A Result type for [`ConfigError`].
@@ -46,17 +122,103 @@ pub struct Config {
     /// A mapping of strings to the gradient for
     /// a single biome.
     pub biomes: HashMap<String, SimpleBiome>,
+    /// A mapping of strings to a gradient that can be
+    /// referred to by name.
+    #[serde(default)]
+    pub palettes: HashMap<String, GradientSpec>,
     /// A mapping of strings to a set of noise generation
     /// parameters.
     pub noise_generators: HashMap<String, Noise>,
     /// A mapping of strings to a set of biomes.
     pub biome_maps: HashMap<String, Biomes>,
+    /// A mapping of strings to a set of moisture model
+    /// parameters.
+    #[serde(default)]
+    pub moisture_models: HashMap<String, MoistureModel>,
+    /// A mapping of strings to a mask and how it is
+    /// blended.
+    #[serde(default)]
+    pub masks: HashMap<String, Mask>,
+    /// A mapping of strings to a set of plate tectonics
+    /// parameters.
+    #[serde(default)]
+    pub tectonics: HashMap<String, Tectonics>,
+    /// A mapping of strings to a set of biomes over any
+    /// number of named factors.
+    #[serde(default)]
+    pub factor_maps: HashMap<String, FactorMap>,
+    /// A mapping of strings to a set of hillshading
+    /// parameters.
+    #[serde(default)]
+    pub hillshades: HashMap<String, Hillshade>,
+    /// A mapping of strings to a set of contour line
+    /// parameters.
+    #[serde(default)]
+    pub contours: HashMap<String, Contours>,
 }
 
 /// The config structure for a single biome gradient.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SimpleBiome {
-    pub gradient: Vec<String>,
+    pub gradient: GradientSpec,
+}
+
+/// The config structure for a gradient. A gradient is either a
+/// list of colors spread evenly from 0-1, a list of colors along
+/// with where they are placed and how they are blended, or a
+/// reference to a named gradient.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum GradientSpec {
+    Colors(Vec<String>),
+    Detailed {
+        colors: Vec<String>,
+        /// The position of each color from 0-1. Defaults to
+        /// spreading the colors evenly.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stops: Option<Vec<f64>>,
+        #[serde(default)]
+        interpolation: GradientInterpolation,
+        #[serde(default)]
+        blend_space: GradientBlendSpace,
+    },
+    /// A reference to a gradient in the palettes section.
+    Palette {
+        palette: String,
+    },
+    /// A reference to the gradient of a biome in the biomes
+    /// section.
+    Biome {
+        biome: String,
+    },
+}
+
+/// The ways that the colors of a gradient can be interpolated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GradientInterpolation {
+    #[default]
+    Linear,
+    /// A B-spline through the colors, which is smooth but doesn't
+    /// pass exactly through each color.
+    Basis,
+    /// A Catmull-Rom spline through the colors.
+    CatmullRom,
+    /// No interpolation, where each color fills the space up to
+    /// the next stop.
+    Step,
+}
+
+/// The color spaces that the colors of a gradient can be
+/// blended in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GradientBlendSpace {
+    #[default]
+    Rgb,
+    LinearRgb,
+    Oklab,
+    Hsv,
 }
 
 /// The config structure for noise generation.
@@ -65,6 +227,90 @@ pub struct Noise {
     pub octaves: usize,
     pub persistence: f64,
     pub lacunarity: f64,
+    #[serde(default = "Noise::default_seed")]
+    pub seed: u32,
+}
+
+/// The config structure for a wind driven moisture model.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MoistureModel {
+    pub evaporation: f64,
+    pub precipitation: f64,
+    pub orographic: f64,
+}
+
+/// The config structure for a mask and how it is blended
+/// with the values it is applied to.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Mask {
+    #[serde(flatten)]
+    pub shape: MaskShape,
+    pub blend: BlendMode,
+    #[serde(default = "Mask::default_strength")]
+    pub strength: f64,
+}
+
+/// The config structure for the shape of a mask.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum MaskShape {
+    Radial {
+        radius: f64,
+        falloff: f64,
+        #[serde(default = "MaskShape::default_center")]
+        center: (f64, f64),
+    },
+    Square {
+        radius: f64,
+        falloff: f64,
+        #[serde(default = "MaskShape::default_center")]
+        center: (f64, f64),
+    },
+    Image {
+        path: String,
+    },
+}
+
+/// The config structure for plate tectonics based elevation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tectonics {
+    pub plates: usize,
+    pub seed: u64,
+    pub continental_fraction: f64,
+    pub boundary_width: f64,
+    /// How much of the elevation comes from the tectonics
+    /// rather than from noise.
+    pub weight: f64,
+}
+
+/// The config structure for lighting the terrain with a
+/// hillshade.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Hillshade {
+    /// The direction of the sun, in degrees clockwise from north.
+    pub azimuth: f64,
+    /// The height of the sun, in degrees above the horizon.
+    pub altitude: f64,
+    pub exaggeration: f64,
+    #[serde(default)]
+    pub multidirectional: bool,
+    /// How strongly shading changes the colors of the map.
+    pub strength: f64,
+}
+
+/// The config structure for contour lines.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Contours {
+    /// The difference in elevation between neighboring contours.
+    pub interval: f64,
+    /// How many contours apart the major lines are.
+    #[serde(default = "Contours::default_major_every")]
+    pub major_every: usize,
+    /// Whether contours are also traced below sea level.
+    #[serde(default)]
+    pub bathymetry: bool,
+    pub color: String,
+    pub major_color: String,
 }
 
 /// The config structure for a set of biome gradients.
@@ -73,6 +319,76 @@ pub struct Noise {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Biomes {
     pub elevation_levels: Vec<ElevationLevel>,
+    /// The width of the band around each threshold, in normalized
+    /// elevation and moisture, over which the colors of neighboring
+    /// biomes are blended. A width of 0 gives hard edges.
+    #[serde(default)]
+    pub blend_width: f64,
+}
+
+/// The config structure for a set of biomes over any number
+/// of named factors.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FactorMap {
+    /// The names of the factors that biomes are chosen by.
+    pub axes: Vec<String>,
+    /// The factor that determines the position within the
+    /// gradient of the chosen biome.
+    pub gradient_factor: String,
+    /// The rule used to choose a biome.
+    #[serde(flatten)]
+    pub rule: FactorRule,
+}
+
+/// The config structure for the ways of choosing a biome from
+/// a set of factors.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum FactorRule {
+    /// Choose a biome by splitting the factors into nested
+    /// ranges.
+    Nested { split: FactorSplit },
+    /// Choose the biome with the nearest centroid.
+    Nearest { biomes: Vec<CentroidBiome> },
+}
+
+/// The config structure for splitting a single factor into
+/// ranges.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FactorSplit {
+    pub factor: String,
+    pub ranges: Vec<FactorRange>,
+}
+
+/// The config structure for a single range of a factor. A range
+/// either splits another factor further or contains a biome.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FactorRange {
+    pub size: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub split: Option<FactorSplit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub biome: Option<FactorBiome>,
+}
+
+/// The config structure for a biome in a factor map.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FactorBiome {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u16>,
+    pub gradient: GradientSpec,
+}
+
+/// The config structure for a biome chosen by the nearest
+/// centroid rule.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CentroidBiome {
+    #[serde(flatten)]
+    pub biome: FactorBiome,
+    /// The value of each factor at the center of the biome.
+    pub centroid: HashMap<String, f64>,
 }
 
 ///  The config structure for a single elevation level.
@@ -86,7 +402,15 @@ pub struct ElevationLevel {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MoistureLevel {
     pub moisture: f64,
-    pub gradient: Vec<String>,
+    pub gradient: GradientSpec,
+    /// The name of the biome covered by this moisture level.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The identifier of the biome covered by this moisture
+    /// level. Defaults to the position of the moisture level
+    /// within the biome map, starting at 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u16>,
 }
 
 impl Config {
@@ -95,6 +419,14 @@ impl Config {
         for pair in self.biomes.iter() {
             let (_, simple_biome) = pair;
             simple_biome.validate()?;
+            resolve_gradient(&simple_biome.gradient, &self.palettes, &self.biomes)?;
+        }
+        for pair in self.palettes.iter() {
+            let (name, palette) = pair;
+            if let GradientSpec::Palette { .. } | GradientSpec::Biome { .. } = palette {
+                return Err(ConfigError::NestedGradientReference(name.clone()));
+            }
+            palette.validate()?;
         }
         for pair in self.noise_generators.iter() {
             let (_, noise_generator) = pair;
@@ -103,6 +435,36 @@ impl Config {
         for pair in self.biome_maps.iter() {
             let (_, biome) = pair;
             biome.validate()?;
+            for gradient in biome.gradients() {
+                resolve_gradient(gradient, &self.palettes, &self.biomes)?;
+            }
+        }
+        for pair in self.moisture_models.iter() {
+            let (_, moisture_model) = pair;
+            moisture_model.validate()?;
+        }
+        for pair in self.masks.iter() {
+            let (_, mask) = pair;
+            mask.validate()?;
+        }
+        for pair in self.tectonics.iter() {
+            let (_, tectonics) = pair;
+            tectonics.validate()?;
+        }
+        for pair in self.hillshades.iter() {
+            let (_, hillshade) = pair;
+            hillshade.validate()?;
+        }
+        for pair in self.contours.iter() {
+            let (_, contours) = pair;
+            contours.validate()?;
+        }
+        for pair in self.factor_maps.iter() {
+            let (_, factor_map) = pair;
+            factor_map.validate()?;
+            for gradient in factor_map.biomes().iter().map(|biome| &biome.gradient) {
+                resolve_gradient(gradient, &self.palettes, &self.biomes)?;
+            }
         }
         Ok(())
     }
@@ -111,16 +473,115 @@ impl Config {
     pub fn from_file(filename: impl AsRef<str>) -> ConfigResult<Self> {
         let file = File::open(filename.as_ref())
             .map_err(|_| ConfigError::InvalidFilePath(String::from(filename.as_ref())))?;
-        let config: Self = serde_yaml::from_reader(file).map_err(|_| ConfigError::FailedToParse)?;
+        let mut config: Self =
+            serde_yaml::from_reader(file).map_err(|_| ConfigError::FailedToParse)?;
+
+        config.resolve_references()?;
+
+        Ok(config)
+    }
+
+    /// Loads the configuration from the bytes of a YAML file.
+    pub fn from_slice(bytes: &[u8]) -> ConfigResult<Self> {
+        let mut config: Self =
+            serde_yaml::from_slice(bytes).map_err(|_| ConfigError::FailedToParse)?;
+
+        config.resolve_references()?;
 
         Ok(config)
     }
 
+    /// Loads the configuration from YAML.
+    pub fn from_yaml(yaml: &str) -> ConfigResult<Self> {
+        let mut config: Self =
+            serde_yaml::from_str(yaml).map_err(|_| ConfigError::FailedToParse)?;
+
+        config.resolve_references()?;
+
+        Ok(config)
+    }
+
+    /// Serializes the configuration as YAML. References to named
+    /// gradients are resolved when a config is loaded, so every
+    /// gradient is written out in full.
+    pub fn to_yaml(&self) -> ConfigResult<String> {
+        serde_yaml::to_string(self).map_err(|_| ConfigError::FailedToSerialize)
+    }
+
+    /// Replaces every reference to a named gradient with the
+    /// gradient that it refers to. This is done when loading a
+    /// config file, but must be done by hand for configs that
+    /// are parsed some other way.
+    pub fn resolve_references(&mut self) -> ConfigResult<()> {
+        let resolved_biomes = self
+            .biomes
+            .iter()
+            .map(|(name, simple_biome)| {
+                resolve_gradient(&simple_biome.gradient, &self.palettes, &self.biomes)
+                    .map(|gradient| (name.clone(), gradient))
+            })
+            .collect::<ConfigResult<Vec<_>>>()?;
+
+        for (name, gradient) in resolved_biomes {
+            if let Some(simple_biome) = self.biomes.get_mut(&name) {
+                simple_biome.gradient = gradient;
+            }
+        }
+        for biomes in self.biome_maps.values_mut() {
+            for gradient in biomes.gradients_mut() {
+                *gradient = resolve_gradient(gradient, &self.palettes, &self.biomes)?;
+            }
+        }
+        for factor_map in self.factor_maps.values_mut() {
+            for gradient in factor_map.gradients_mut() {
+                *gradient = resolve_gradient(gradient, &self.palettes, &self.biomes)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the seed of every noise generator and of the plate
+    /// tectonics so that a whole map can be varied by a single seed.
+    /// Each generator is given its own seed derived from `seed` and
+    /// its name, so that the generators don't produce the same noise.
+    pub fn reseed(&mut self, seed: u32) {
+        let derive = |key: String| {
+            let mut bytes = seed.to_le_bytes().to_vec();
+
+            bytes.extend(key.as_bytes());
+            hash_bytes(&bytes)
+        };
+
+        for (name, noise) in self.noise_generators.iter_mut() {
+            noise.seed = derive(format!("noise_generators.{name}")) as u32;
+        }
+        for (name, tectonics) in self.tectonics.iter_mut() {
+            tectonics.seed = derive(format!("tectonics.{name}"));
+        }
+    }
+
+    /// Gets the seed of every noise generator and of the plate
+    /// tectonics, keyed by the section and name that they are found
+    /// under, such as `noise_generators.elevation_noise`.
+    pub fn seeds(&self) -> BTreeMap<String, u64> {
+        let noise = self
+            .noise_generators
+            .iter()
+            .map(|(name, noise)| (format!("noise_generators.{name}"), noise.seed as u64));
+        let tectonics = self
+            .tectonics
+            .iter()
+            .map(|(name, tectonics)| (format!("tectonics.{name}"), tectonics.seed));
+
+        noise.chain(tectonics).collect()
+    }
+
     /// Returns the associated noise generator for a given [`Noise`].
     ///
     /// Type parameters:
     /// - B - the noise generator builder type to use to
     ///     construct the noise generator.
+    #[allow(clippy::doc_overindented_list_items)]
     pub fn get_noise_generator<B: NoiseGeneratorBuilder>(
         &self,
         name: impl AsRef<str>,
@@ -133,6 +594,7 @@ impl Config {
                     .octaves(noise.octaves)
                     .persistence(noise.persistence)
                     .lacunarity(noise.lacunarity)
+                    .seed(noise.seed)
                     .build(),
             )
         } else {
@@ -140,7 +602,120 @@ impl Config {
         }
     }
 
+    /// Returns the associated moisture model for a given [`MoistureModel`].
+    pub fn get_moisture_model(&self, name: impl AsRef<str>) -> Option<WindMoistureModel> {
+        self.moisture_models
+            .get(name.as_ref())
+            .map(|moisture_model| {
+                WindMoistureModelBuilder::new()
+                    .evaporation(moisture_model.evaporation)
+                    .precipitation(moisture_model.precipitation)
+                    .orographic(moisture_model.orographic)
+                    .build()
+            })
+    }
+
+    /// Returns the associated hillshade for a given [`Hillshade`].
+    pub fn get_hillshade(&self, name: impl AsRef<str>) -> Option<terrain::Hillshade> {
+        self.hillshades.get(name.as_ref()).map(|hillshade| {
+            HillshadeBuilder::new()
+                .azimuth(hillshade.azimuth)
+                .altitude(hillshade.altitude)
+                .exaggeration(hillshade.exaggeration)
+                .multidirectional(hillshade.multidirectional)
+                .strength(hillshade.strength)
+                .build()
+        })
+    }
+
+    /// Returns the associated contour generator for a given
+    /// [`Contours`]. Contours are counted from `sea_level`.
+    pub fn get_contours(&self, name: impl AsRef<str>, sea_level: f64) -> Option<ContourGenerator> {
+        let contours = self.contours.get(name.as_ref())?;
+        let color = |color: &str| {
+            Color::from_html(color)
+                .ok()
+                .map(|color| Rgba(color.to_rgba8()))
+        };
+
+        Some(
+            ContourGeneratorBuilder::new(contours.interval)
+                .sea_level(sea_level)
+                .major_every(contours.major_every)
+                .bathymetry(contours.bathymetry)
+                .color(color(&contours.color)?)
+                .major_color(color(&contours.major_color)?)
+                .build(),
+        )
+    }
+
+    /// Returns the associated mask layer for a given [`Mask`], or
+    /// `None` if there is no such mask. Fails if the mask is an image
+    /// that couldn't be loaded.
+    pub fn get_mask(
+        &self,
+        name: impl AsRef<str>,
+        width: usize,
+        height: usize,
+    ) -> ConfigResult<Option<MaskLayer>> {
+        let Some(mask) = self.masks.get(name.as_ref()) else {
+            return Ok(None);
+        };
+        let generator: Box<dyn SimpleNoiseGenerator + Send + Sync> = match &mask.shape {
+            MaskShape::Radial {
+                radius,
+                falloff,
+                center,
+            } => Box::new(RadialFalloff::new(
+                width, height, *center, *radius, *falloff,
+            )),
+            MaskShape::Square {
+                radius,
+                falloff,
+                center,
+            } => Box::new(SquareFalloff::new(
+                width, height, *center, *radius, *falloff,
+            )),
+            MaskShape::Image { path } => {
+                Box::new(ImageMask::from_file(path, width, height).map_err(|error| {
+                    ConfigError::InvalidMaskImage(path.clone(), error.to_string())
+                })?)
+            }
+        };
+
+        Ok(Some(MaskLayer::new(generator, mask.blend, mask.strength)))
+    }
+
+    /// Returns the associated tectonics based elevation for a given
+    /// [`Tectonics`]. The elevation is returned as a layer that mixes
+    /// it into an existing elevation according to its weight.
+    pub fn get_tectonics(
+        &self,
+        name: impl AsRef<str>,
+        width: usize,
+        height: usize,
+    ) -> Option<MaskLayer> {
+        self.tectonics.get(name.as_ref()).map(|tectonics| {
+            let generator = TectonicsGeneratorBuilder::new(width, height)
+                .plates(tectonics.plates)
+                .seed(tectonics.seed)
+                .continental_fraction(tectonics.continental_fraction)
+                .boundary_width(tectonics.boundary_width)
+                .build();
+
+            MaskLayer::new(Box::new(generator), BlendMode::Replace, tectonics.weight)
+        })
+    }
+
+    /// Returns a biome classifier for a given set of biome mappings.
+    pub fn get_biome_classifier(&self, name: impl AsRef<str>) -> Option<BiomeClassifier> {
+        self.biome_maps
+            .get(name.as_ref())
+            .map(BiomeClassifier::from_biomes)
+    }
+
     /// Returns a color evaluator for a given set of biome mappings.
+    #[allow(clippy::manual_ok_err)]
     pub fn get_color_evaluator(&self, name: impl AsRef<str>) -> Option<ColorEvaluator> {
         if let Some(biomes) = self.biome_maps.get(name.as_ref()) {
             if let Ok(evaluator) = ColorEvaluator::from_biomes(biomes) {
@@ -153,7 +728,18 @@ impl Config {
         }
     }
 
+    /// Returns a factor evaluator for a given set of biomes over
+    /// named factors.
+    pub fn get_factor_evaluator(&self, name: impl AsRef<str>) -> Option<FactorEvaluator> {
+        if let Some(factor_map) = self.factor_maps.get(name.as_ref()) {
+            FactorEvaluator::from_factor_map(factor_map).ok()
+        } else {
+            None
+        }
+    }
+
     /// Returns a color function for a given biome.
+    #[allow(clippy::manual_ok_err)]
     pub fn get_color_func(&self, name: impl AsRef<str>) -> Option<ColorFunc> {
         if let Some(biome) = self.biomes.get(name.as_ref()) {
             if let Ok(color_func) = get_color_func(&biome.gradient) {
@@ -167,20 +753,115 @@ impl Config {
     }
 }
 
+/// Finds the gradient that a gradient refers to. Palettes must be
+/// written out in full, while the gradient of a biome may refer to
+/// a palette.
+fn resolve_gradient(
+    gradient: &GradientSpec,
+    palettes: &HashMap<String, GradientSpec>,
+    biomes: &HashMap<String, SimpleBiome>,
+) -> ConfigResult<GradientSpec> {
+    match gradient {
+        GradientSpec::Palette { palette } => match palettes.get(palette) {
+            Some(GradientSpec::Palette { .. } | GradientSpec::Biome { .. }) => {
+                Err(ConfigError::NestedGradientReference(palette.clone()))
+            }
+            Some(gradient) => Ok(gradient.clone()),
+            None => Err(ConfigError::UnknownPalette(palette.clone())),
+        },
+        GradientSpec::Biome { biome } => match biomes.get(biome) {
+            Some(SimpleBiome {
+                gradient: GradientSpec::Biome { .. },
+            }) => Err(ConfigError::NestedGradientReference(biome.clone())),
+            Some(simple_biome) => resolve_gradient(&simple_biome.gradient, palettes, biomes),
+            None => Err(ConfigError::UnknownBiome(biome.clone())),
+        },
+        gradient => Ok(gradient.clone()),
+    }
+}
+
 impl SimpleBiome {
     /// Validate the parameters for a single biome.
     fn validate(&self) -> ConfigResult<()> {
-        if self.gradient.is_empty() {
+        self.gradient.validate()
+    }
+}
+
+impl GradientSpec {
+    /// Validate the gradient. References to named gradients are
+    /// checked when validating the entire configuration.
+    fn validate(&self) -> ConfigResult<()> {
+        if let GradientSpec::Palette { .. } | GradientSpec::Biome { .. } = self {
+            return Ok(());
+        }
+
+        let colors = self.colors();
+
+        if colors.is_empty() {
             return Err(ConfigError::MissingColors);
         }
-        for color in &self.gradient {
+        for color in colors {
             Color::from_html(color).map_err(|_| ConfigError::InvalidColor(color.to_string()))?;
         }
+        if let Some(stops) = self.stops() {
+            if stops.len() != colors.len() {
+                return Err(ConfigError::MismatchedStops(stops.len(), colors.len()));
+            }
+            for stop in stops {
+                if !(0.0..=1.0).contains(stop) {
+                    return Err(ConfigError::InvalidStop(*stop));
+                }
+            }
+            if stops.windows(2).any(|pair| pair[0] > pair[1]) {
+                return Err(ConfigError::UnorderedStops);
+            }
+        }
         Ok(())
     }
+
+    /// Gets the colors of the gradient. References to named
+    /// gradients have no colors until they are resolved.
+    pub fn colors(&self) -> &[String] {
+        match self {
+            GradientSpec::Colors(colors) => colors,
+            GradientSpec::Detailed { colors, .. } => colors,
+            GradientSpec::Palette { .. } | GradientSpec::Biome { .. } => &[],
+        }
+    }
+
+    /// Gets the position of each color of the gradient, if they
+    /// aren't spread evenly.
+    pub fn stops(&self) -> Option<&[f64]> {
+        match self {
+            GradientSpec::Detailed { stops, .. } => stops.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Gets how the colors of the gradient are interpolated.
+    pub fn interpolation(&self) -> GradientInterpolation {
+        match self {
+            GradientSpec::Detailed { interpolation, .. } => *interpolation,
+            _ => GradientInterpolation::default(),
+        }
+    }
+
+    /// Gets the color space that the colors of the gradient are
+    /// blended in.
+    pub fn blend_space(&self) -> GradientBlendSpace {
+        match self {
+            GradientSpec::Detailed { blend_space, .. } => *blend_space,
+            _ => GradientBlendSpace::default(),
+        }
+    }
 }
 
 impl Noise {
+    /// The seed of noise generators that don't specify one.
+    fn default_seed() -> u32 {
+        2
+    }
+
     /// Validate the noise generation config items.
     fn validate(&self) -> ConfigResult<()> {
         if self.persistence <= 0.0 {
@@ -193,9 +874,127 @@ impl Noise {
     }
 }
 
+impl MoistureModel {
+    /// Validate the moisture model config items.
+    fn validate(&self) -> ConfigResult<()> {
+        if !(0.0..=1.0).contains(&self.evaporation) {
+            return Err(ConfigError::InvalidEvaporation(self.evaporation));
+        }
+        if !(0.0..=1.0).contains(&self.precipitation) {
+            return Err(ConfigError::InvalidPrecipitation(self.precipitation));
+        }
+        if self.orographic < 0.0 {
+            return Err(ConfigError::InvalidOrographic(self.orographic));
+        }
+        Ok(())
+    }
+}
+
+impl Hillshade {
+    /// Validate the hillshade config items.
+    fn validate(&self) -> ConfigResult<()> {
+        if !(0.0..=90.0).contains(&self.altitude) {
+            return Err(ConfigError::InvalidAltitude(self.altitude));
+        }
+        if self.exaggeration <= 0.0 {
+            return Err(ConfigError::InvalidExaggeration(self.exaggeration));
+        }
+        if !(0.0..=1.0).contains(&self.strength) {
+            return Err(ConfigError::InvalidStrength(self.strength));
+        }
+        Ok(())
+    }
+}
+
+impl Contours {
+    /// The number of contours between major lines for contours that
+    /// don't specify one.
+    fn default_major_every() -> usize {
+        5
+    }
+
+    /// Validate the contour config items.
+    fn validate(&self) -> ConfigResult<()> {
+        if self.interval <= 0.0 {
+            return Err(ConfigError::InvalidInterval(self.interval));
+        }
+        if self.major_every == 0 {
+            return Err(ConfigError::InvalidMajorEvery(self.major_every));
+        }
+        for color in [&self.color, &self.major_color] {
+            Color::from_html(color).map_err(|_| ConfigError::InvalidColor(color.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+impl Mask {
+    /// The strength of a mask that doesn't specify one.
+    fn default_strength() -> f64 {
+        1.0
+    }
+
+    /// Validate the mask config items.
+    fn validate(&self) -> ConfigResult<()> {
+        if !(0.0..=1.0).contains(&self.strength) {
+            return Err(ConfigError::InvalidStrength(self.strength));
+        }
+        match &self.shape {
+            MaskShape::Radial {
+                radius, falloff, ..
+            }
+            | MaskShape::Square {
+                radius, falloff, ..
+            } => {
+                if *radius <= 0.0 {
+                    return Err(ConfigError::InvalidRadius(*radius));
+                }
+                if !(0.0..=1.0).contains(falloff) {
+                    return Err(ConfigError::InvalidFalloff(*falloff));
+                }
+            }
+            MaskShape::Image { path } => {
+                File::open(path).map_err(|_| ConfigError::InvalidFilePath(path.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MaskShape {
+    /// The center of a mask that doesn't specify one.
+    fn default_center() -> (f64, f64) {
+        (0.5, 0.5)
+    }
+}
+
+impl Tectonics {
+    /// Validate the plate tectonics config items.
+    fn validate(&self) -> ConfigResult<()> {
+        if self.plates == 0 {
+            return Err(ConfigError::InvalidPlates(self.plates));
+        }
+        if !(0.0..=1.0).contains(&self.continental_fraction) {
+            return Err(ConfigError::InvalidContinentalFraction(
+                self.continental_fraction,
+            ));
+        }
+        if self.boundary_width <= 0.0 {
+            return Err(ConfigError::InvalidBoundaryWidth(self.boundary_width));
+        }
+        if !(0.0..=1.0).contains(&self.weight) {
+            return Err(ConfigError::InvalidWeight(self.weight));
+        }
+        Ok(())
+    }
+}
+
 impl Biomes {
     /// Validate the biomes.
-    fn validate(&self) -> ConfigResult<()> {
+    pub(crate) fn validate(&self) -> ConfigResult<()> {
+        if self.blend_width < 0.0 {
+            return Err(ConfigError::InvalidBlendWidth(self.blend_width));
+        }
         if self.elevation_levels.is_empty() {
             return Err(ConfigError::MissingElevationLevels);
         } else {
@@ -203,15 +1002,191 @@ impl Biomes {
                 elevation_level.validate()?;
             }
         }
+
+        let mut ids = self.biome_ids();
+
+        ids.sort();
+        for pair in ids.windows(2) {
+            if pair[0] == pair[1] {
+                return Err(ConfigError::DuplicateBiomeId(pair[0].0));
+            }
+        }
         Ok(())
     }
 
+    /// Gets the identifiers of every biome in the biome mapping, in
+    /// the order that they are declared.
+    pub(crate) fn biome_ids(&self) -> Vec<BiomeId> {
+        self.elevation_levels
+            .iter()
+            .flat_map(|level| level.moisture_levels.iter())
+            .enumerate()
+            .map(|(index, level)| BiomeId(level.id.unwrap_or(index as u16 + 1)))
+            .collect()
+    }
+
     /// Gets the total elevation in the biome mapping.
     pub(crate) fn total_elevation(&self) -> f64 {
         self.elevation_levels
             .iter()
             .fold(0.0, |acc, level| acc + level.elevation)
     }
+
+    /// Gets the gradient of every biome in the biome mapping.
+    fn gradients(&self) -> impl Iterator<Item = &GradientSpec> {
+        self.elevation_levels
+            .iter()
+            .flat_map(|level| level.moisture_levels.iter())
+            .map(|level| &level.gradient)
+    }
+
+    /// Gets the gradient of every biome in the biome mapping
+    /// mutably.
+    fn gradients_mut(&mut self) -> impl Iterator<Item = &mut GradientSpec> {
+        self.elevation_levels
+            .iter_mut()
+            .flat_map(|level| level.moisture_levels.iter_mut())
+            .map(|level| &mut level.gradient)
+    }
+}
+
+impl FactorMap {
+    /// Validate the factor map.
+    pub(crate) fn validate(&self) -> ConfigResult<()> {
+        self.validate_axis(&self.gradient_factor)?;
+        match &self.rule {
+            FactorRule::Nested { split } => split.validate(self)?,
+            FactorRule::Nearest { biomes } => {
+                if biomes.is_empty() {
+                    return Err(ConfigError::MissingBiomes);
+                }
+                for centroid_biome in biomes {
+                    centroid_biome.biome.validate()?;
+                    for axis in centroid_biome.centroid.keys() {
+                        self.validate_axis(axis)?;
+                    }
+                    for axis in &self.axes {
+                        if !centroid_biome.centroid.contains_key(axis) {
+                            return Err(ConfigError::MissingCentroidAxis(axis.to_string()));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut ids = self.biome_ids();
+
+        ids.sort();
+        for pair in ids.windows(2) {
+            if pair[0] == pair[1] {
+                return Err(ConfigError::DuplicateBiomeId(pair[0].0));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate that a factor is one of the declared axes.
+    fn validate_axis(&self, factor: &str) -> ConfigResult<()> {
+        if !self.axes.iter().any(|axis| axis == factor) {
+            return Err(ConfigError::UnknownAxis(factor.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Gets every biome in the factor map, in the order that they
+    /// are declared.
+    pub(crate) fn biomes(&self) -> Vec<&FactorBiome> {
+        match &self.rule {
+            FactorRule::Nested { split } => split.biomes(),
+            FactorRule::Nearest { biomes } => biomes.iter().map(|biome| &biome.biome).collect(),
+        }
+    }
+
+    /// Gets the gradient of every biome in the factor map mutably.
+    fn gradients_mut(&mut self) -> Vec<&mut GradientSpec> {
+        match &mut self.rule {
+            FactorRule::Nested { split } => split
+                .biomes_mut()
+                .into_iter()
+                .map(|biome| &mut biome.gradient)
+                .collect(),
+            FactorRule::Nearest { biomes } => biomes
+                .iter_mut()
+                .map(|biome| &mut biome.biome.gradient)
+                .collect(),
+        }
+    }
+
+    /// Gets the identifiers of every biome in the factor map, in
+    /// the order that they are declared.
+    pub(crate) fn biome_ids(&self) -> Vec<BiomeId> {
+        self.biomes()
+            .iter()
+            .enumerate()
+            .map(|(index, biome)| BiomeId(biome.id.unwrap_or(index as u16 + 1)))
+            .collect()
+    }
+}
+
+impl FactorSplit {
+    /// Validate the split and every range within it.
+    fn validate(&self, factor_map: &FactorMap) -> ConfigResult<()> {
+        factor_map.validate_axis(&self.factor)?;
+        if self.ranges.is_empty() {
+            return Err(ConfigError::MissingFactorRanges);
+        }
+        for range in &self.ranges {
+            if range.size <= 0.0 {
+                return Err(ConfigError::InvalidSize(range.size));
+            }
+            match (&range.split, &range.biome) {
+                (Some(split), None) => split.validate(factor_map)?,
+                (None, Some(biome)) => biome.validate()?,
+                (None, None) => {}
+                (Some(_), Some(_)) => return Err(ConfigError::InvalidFactorRange),
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets every biome within the split, depth first.
+    fn biomes(&self) -> Vec<&FactorBiome> {
+        self.ranges
+            .iter()
+            .flat_map(|range| match (&range.split, &range.biome) {
+                (Some(split), _) => split.biomes(),
+                (None, Some(biome)) => vec![biome],
+                (None, None) => vec![],
+            })
+            .collect()
+    }
+
+    /// Gets every biome within the split mutably, depth first.
+    fn biomes_mut(&mut self) -> Vec<&mut FactorBiome> {
+        self.ranges
+            .iter_mut()
+            .flat_map(|range| match (&mut range.split, &mut range.biome) {
+                (Some(split), _) => split.biomes_mut(),
+                (None, Some(biome)) => vec![biome],
+                (None, None) => vec![],
+            })
+            .collect()
+    }
+
+    /// Gets the total size of the ranges in the split.
+    pub(crate) fn total_size(&self) -> f64 {
+        self.ranges.iter().fold(0.0, |acc, range| acc + range.size)
+    }
+}
+
+impl FactorBiome {
+    /// Validate the biome.
+    fn validate(&self) -> ConfigResult<()> {
+        if self.id == Some(BiomeId::NONE.0) {
+            return Err(ConfigError::InvalidBiomeId(BiomeId::NONE.0));
+        }
+        self.gradient.validate()
+    }
 }
 
 impl ElevationLevel {
@@ -243,12 +1218,112 @@ impl MoistureLevel {
         if self.moisture <= 0.0 {
             return Err(ConfigError::InvalidMoisture(self.moisture));
         }
-        if self.gradient.is_empty() {
-            return Err(ConfigError::MissingColors);
-        }
-        for color in &self.gradient {
-            Color::from_html(color).map_err(|_| ConfigError::InvalidColor(color.to_string()))?;
+        if self.id == Some(BiomeId::NONE.0) {
+            return Err(ConfigError::InvalidBiomeId(BiomeId::NONE.0));
         }
-        Ok(())
+        self.gradient.validate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(gradient: &str) -> Config {
+        serde_yaml::from_str(&format!(
+            r##"
+            biomes:
+              ocean:
+                gradient: {{ palette: deep }}
+            palettes:
+              deep: ["#0a46ad", "#35d6f2"]
+            noise_generators: {{}}
+            biome_maps:
+              default:
+                elevation_levels:
+                  - elevation: 1.0
+                    moisture_levels:
+                      - moisture: 1.0
+                        gradient: {gradient}
+            "##
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_references() {
+        let mut config = config("{ biome: ocean }");
+
+        assert!(config.validate().is_ok());
+
+        config.resolve_references().unwrap();
+
+        let gradient =
+            &config.biome_maps["default"].elevation_levels[0].moisture_levels[0].gradient;
+
+        assert_eq!(gradient, &config.palettes["deep"]);
+        assert_eq!(config.biomes["ocean"].gradient, config.palettes["deep"]);
+    }
+
+    #[test]
+    fn test_unknown_references_are_invalid() {
+        assert!(matches!(
+            config("{ palette: shallow }").validate(),
+            Err(ConfigError::UnknownPalette(name)) if name == "shallow"
+        ));
+        assert!(matches!(
+            config("{ biome: land }").validate(),
+            Err(ConfigError::UnknownBiome(name)) if name == "land"
+        ));
+    }
+
+    #[test]
+    fn test_reseed_gives_each_generator_its_own_seed() {
+        let mut config: Config = serde_yaml::from_str(
+            r##"
+            biomes: {}
+            noise_generators:
+              elevation_noise: { octaves: 4, persistence: 0.5, lacunarity: 2.0 }
+              moisture_noise: { octaves: 4, persistence: 0.5, lacunarity: 2.0 }
+            biome_maps: {}
+            tectonics:
+              elevation_tectonics:
+                plates: 8
+                seed: 0
+                continental_fraction: 0.4
+                boundary_width: 0.05
+                weight: 0.5
+            "##,
+        )
+        .unwrap();
+
+        config.reseed(7);
+
+        let seeds = config.seeds();
+
+        config.reseed(7);
+
+        assert_eq!(config.seeds(), seeds);
+        assert_ne!(
+            seeds["noise_generators.elevation_noise"],
+            seeds["noise_generators.moisture_noise"]
+        );
+        assert_eq!(seeds.len(), 3);
+    }
+
+    #[test]
+    fn test_unreadable_mask_image_fails() {
+        let mut config = config("[\"#ffffff\"]");
+
+        config.masks.insert(
+            String::from("elevation_mask"),
+            serde_yaml::from_str("{ shape: image, path: Cargo.toml, blend: multiply }").unwrap(),
+        );
+
+        assert!(config.get_mask("missing", 4, 4).unwrap().is_none());
+        assert!(matches!(
+            config.get_mask("elevation_mask", 4, 4),
+            Err(ConfigError::InvalidMaskImage(path, _)) if path == "Cargo.toml"
+        ));
     }
 }
@@ -0,0 +1,498 @@
+//! This module provides contour lines, which trace the paths of
+//! equal elevation across a map, for drawing topographic maps.
+//!
+//! Contours are traced with marching squares over the centers of
+//! the cells of a map, and joined into polylines that can be drawn
+//! onto a rendered map or exported as vectors. Contours below sea
+//! level trace the depth of the ocean floor, which are also known
+//! as isobaths.
+//!
+//! Points are measured in cells, where the center of the cell at
+//! `(x, y)` is at `(x + 0.5, y + 0.5)`. Contours that cross the
+//! east-west edges of the map are split where they wrap.
+//!
+//! # Examples
+//!
+//! ```
+//! use ficture::contour::ContourGeneratorBuilder;
+//! use ficture::map::{Map, MapMonad};
+//!
+//! let elevation = Map::return_single(0.0, 20, 20);
+//! let elevation = elevation.and_then_with_coordinates(|_, x, y| {
+//!     let (dx, dy) = (x as f64 - 10.0, y as f64 - 10.0);
+//!
+//!     1.0 - (dx * dx + dy * dy).sqrt() / 10.0
+//! });
+//!
+//! let generator = ContourGeneratorBuilder::new(0.25).sea_level(0.0).build();
+//! let contours = generator.generate(&elevation);
+//! ```
+use std::collections::HashMap;
+
+use image::Rgba;
+
+use crate::{map::Map, utils::wrap};
+
+/// A line through a series of points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline {
+    pub points: Vec<(f64, f64)>,
+    /// Whether the last point joins back up with the first.
+    pub closed: bool,
+}
+
+/// Every contour at a single elevation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContourLine {
+    /// The elevation that the contour traces.
+    pub level: f64,
+    /// Whether the contour is a major line, which are usually drawn
+    /// more prominently than the minor lines between them.
+    pub major: bool,
+    pub polylines: Vec<Polyline>,
+}
+
+/// An edge between two neighboring cell centers that a contour
+/// crosses. Horizontal edges join a cell to the cell east of it and
+/// vertical edges join a cell to the cell south of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Edge {
+    Horizontal(usize, usize),
+    Vertical(usize, usize),
+}
+
+/// Gets the point where a contour at `level` crosses an edge.
+fn crossing(elevation: &Map<f64>, level: f64, edge: Edge) -> (f64, f64) {
+    let (x, y, next) = match edge {
+        Edge::Horizontal(x, y) => (x, y, (wrap(x as isize + 1, elevation.width()), y)),
+        Edge::Vertical(x, y) => (x, y, (x, y + 1)),
+    };
+    let (a, b) = (*elevation.get(x, y), *elevation.get(next.0, next.1));
+    let t = if a == b { 0.5 } else { (level - a) / (b - a) };
+
+    match edge {
+        Edge::Horizontal(..) => (x as f64 + t + 0.5, y as f64 + 0.5),
+        Edge::Vertical(..) => (x as f64 + 0.5, y as f64 + t + 0.5),
+    }
+}
+
+/// Gets the segments of contour crossing every square between four
+/// neighboring cell centers, as pairs of the edges they join.
+fn segments(elevation: &Map<f64>, level: f64) -> Vec<(Edge, Edge)> {
+    let (width, height) = (elevation.width(), elevation.height());
+    let mut segments = vec![];
+
+    for y in 0..height.saturating_sub(1) {
+        for x in 0..width {
+            let next_x = wrap(x as isize + 1, width);
+            let corners = [
+                *elevation.get(x, y),
+                *elevation.get(next_x, y),
+                *elevation.get(next_x, y + 1),
+                *elevation.get(x, y + 1),
+            ];
+            let case = corners.iter().enumerate().fold(0, |case, (i, corner)| {
+                case | ((*corner >= level) as usize) << i
+            });
+
+            let top = Edge::Horizontal(x, y);
+            let right = Edge::Vertical(next_x, y);
+            let bottom = Edge::Horizontal(x, y + 1);
+            let left = Edge::Vertical(x, y);
+            // saddles are resolved by the average of the corners
+            let center_high = corners.iter().sum::<f64>() / 4.0 >= level;
+
+            match case {
+                1 | 14 => segments.push((left, top)),
+                2 | 13 => segments.push((top, right)),
+                3 | 12 => segments.push((left, right)),
+                4 | 11 => segments.push((right, bottom)),
+                6 | 9 => segments.push((top, bottom)),
+                7 | 8 => segments.push((left, bottom)),
+                5 if center_high => segments.extend([(top, right), (bottom, left)]),
+                10 if !center_high => segments.extend([(top, right), (bottom, left)]),
+                5 | 10 => segments.extend([(left, top), (right, bottom)]),
+                _ => {}
+            }
+        }
+    }
+
+    segments
+}
+
+/// Traces every contour at a single elevation.
+pub fn trace_contours(elevation: &Map<f64>, level: f64) -> Vec<Polyline> {
+    let width = elevation.width() as f64;
+    let segments = segments(elevation, level);
+    let mut by_edge: HashMap<Edge, Vec<usize>> = HashMap::new();
+    let mut used = vec![false; segments.len()];
+    let mut polylines = vec![];
+
+    for (index, (a, b)) in segments.iter().enumerate() {
+        by_edge.entry(*a).or_default().push(index);
+        by_edge.entry(*b).or_default().push(index);
+    }
+
+    // follows the unused segments joined end to end from `edge`
+    let follow = |mut edge: Edge, used: &mut Vec<bool>| {
+        let mut edges = vec![];
+
+        while let Some(next) = by_edge[&edge].iter().find(|index| !used[**index]) {
+            let (a, b) = segments[*next];
+
+            used[*next] = true;
+            edge = if a == edge { b } else { a };
+            edges.push(edge);
+        }
+
+        edges
+    };
+
+    for start in 0..segments.len() {
+        if used[start] {
+            continue;
+        }
+
+        used[start] = true;
+
+        let (a, b) = segments[start];
+        let mut edges = follow(a, &mut used);
+
+        edges.reverse();
+        edges.push(a);
+        edges.push(b);
+        edges.extend(follow(b, &mut used));
+
+        let closed = edges.len() > 2 && edges.first() == edges.last();
+
+        if closed {
+            edges.pop();
+        }
+
+        let points: Vec<(f64, f64)> = edges
+            .iter()
+            .map(|edge| crossing(elevation, level, *edge))
+            .collect();
+
+        // split the line wherever it wraps around the edges of the map
+        let mut pieces: Vec<Vec<(f64, f64)>> = vec![vec![]];
+
+        for point in points {
+            let piece = pieces.last_mut().expect("at least one piece");
+
+            match piece.last() {
+                Some((x, _)) if (point.0 - x).abs() > width / 2.0 => pieces.push(vec![point]),
+                _ => piece.push(point),
+            }
+        }
+
+        let split = pieces.len() > 1;
+
+        // a closed line that was split continues from its last piece
+        // into its first
+        if closed && split {
+            let first = pieces.remove(0);
+            let last = pieces.last_mut().expect("at least one piece");
+
+            match (last.last(), first.first()) {
+                (Some((a, _)), Some((b, _))) if (a - b).abs() > width / 2.0 => {
+                    pieces.insert(0, first)
+                }
+                _ => last.extend(first),
+            }
+        }
+
+        polylines.extend(pieces.into_iter().map(|points| Polyline {
+            points,
+            closed: closed && !split,
+        }));
+    }
+
+    polylines
+}
+
+/// A structure for generating and drawing contours at regular
+/// intervals above and below sea level.
+pub struct ContourGenerator {
+    sea_level: f64,
+    interval: f64,
+    major_every: usize,
+    bathymetry: bool,
+    color: Rgba<u8>,
+    major_color: Rgba<u8>,
+}
+
+impl ContourGenerator {
+    /// Gets the elevation of every contour, along with whether it is
+    /// a major line. The coastline is always a major line.
+    pub fn levels(&self) -> Vec<(f64, bool)> {
+        let mut levels = vec![];
+        let major = |step: usize| step.is_multiple_of(self.major_every.max(1));
+
+        if self.interval <= 0.0 {
+            return levels;
+        }
+
+        if self.bathymetry {
+            let mut step = 1;
+
+            while self.sea_level - step as f64 * self.interval >= 0.0 {
+                levels.push((self.sea_level - step as f64 * self.interval, major(step)));
+                step += 1;
+            }
+
+            levels.reverse();
+        }
+
+        let mut step = 0;
+
+        while self.sea_level + step as f64 * self.interval <= 1.0 {
+            levels.push((self.sea_level + step as f64 * self.interval, major(step)));
+            step += 1;
+        }
+
+        levels
+    }
+
+    /// Traces every contour of a map.
+    pub fn generate(&self, elevation: &Map<f64>) -> Vec<ContourLine> {
+        self.levels()
+            .into_iter()
+            .map(|(level, major)| ContourLine {
+                level,
+                major,
+                polylines: trace_contours(elevation, level),
+            })
+            .collect()
+    }
+
+    /// Gets the color that major or minor contours are drawn with.
+    pub fn color(&self, major: bool) -> Rgba<u8> {
+        if major {
+            self.major_color
+        } else {
+            self.color
+        }
+    }
+
+    /// Draws contours onto a map of colors, blending the colors of
+    /// the contours by their transparency.
+    pub fn draw(&self, colors: Map<Rgba<u8>>, contours: &[ContourLine]) -> Map<Rgba<u8>> {
+        let (width, height) = (colors.width(), colors.height());
+        let mut pixels = colors.to_vec();
+
+        for contour in contours {
+            let color = self.color(contour.major);
+            let alpha = color.0[3] as f64 / 255.0;
+            let mut plot = |x: f64, y: f64| {
+                let (x, y) = (wrap(x.floor() as isize, width), y.floor() as usize);
+
+                if y < height {
+                    let pixel = &mut pixels[y * width + x];
+
+                    for i in 0..3 {
+                        pixel.0[i] = (pixel.0[i] as f64 * (1.0 - alpha) + color.0[i] as f64 * alpha)
+                            .round() as u8;
+                    }
+                    pixel.0[3] = pixel.0[3].max(color.0[3]);
+                }
+            };
+
+            for polyline in &contour.polylines {
+                let mut points = polyline.points.clone();
+
+                if polyline.closed {
+                    points.extend(polyline.points.first());
+                }
+
+                for pair in points.windows(2) {
+                    let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+                    let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0) as usize;
+
+                    for step in 0..steps {
+                        let t = step as f64 / steps as f64;
+
+                        plot(x0 + (x1 - x0) * t, y0 + (y1 - y0) * t);
+                    }
+                }
+            }
+        }
+
+        Map::from_vec(pixels, width, height)
+    }
+}
+
+/// A builder for the [`ContourGenerator`].
+pub struct ContourGeneratorBuilder {
+    sea_level: f64,
+    interval: f64,
+    major_every: usize,
+    bathymetry: bool,
+    color: Rgba<u8>,
+    major_color: Rgba<u8>,
+}
+
+impl ContourGeneratorBuilder {
+    /// Creates the [`ContourGeneratorBuilder`] with contours every
+    /// `interval` of elevation.
+    pub fn new(interval: f64) -> Self {
+        Self {
+            sea_level: 0.0,
+            interval,
+            major_every: 5,
+            bathymetry: false,
+            color: Rgba([0, 0, 0, 64]),
+            major_color: Rgba([0, 0, 0, 128]),
+        }
+    }
+
+    /// Sets the sea level, which contours are counted from.
+    pub fn sea_level(mut self, sea_level: f64) -> Self {
+        self.sea_level = sea_level;
+        self
+    }
+
+    /// Sets how many contours apart the major lines are.
+    pub fn major_every(mut self, major_every: usize) -> Self {
+        self.major_every = major_every;
+        self
+    }
+
+    /// Sets whether contours are also traced below sea level.
+    pub fn bathymetry(mut self, bathymetry: bool) -> Self {
+        self.bathymetry = bathymetry;
+        self
+    }
+
+    /// Sets the color of the minor lines.
+    pub fn color(mut self, color: Rgba<u8>) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets the color of the major lines.
+    pub fn major_color(mut self, major_color: Rgba<u8>) -> Self {
+        self.major_color = major_color;
+        self
+    }
+
+    /// Construct the [`ContourGenerator`] based on the
+    /// defined attributes.
+    pub fn build(self) -> ContourGenerator {
+        ContourGenerator {
+            sea_level: self.sea_level,
+            interval: self.interval,
+            major_every: self.major_every,
+            bathymetry: self.bathymetry,
+            color: self.color,
+            major_color: self.major_color,
+        }
+    }
+}
+
+/// Exports contours as a GeoJSON feature collection of line
+/// strings, with the elevation of each contour and whether it is a
+/// major line as properties. Coordinates are measured in cells.
+pub fn contours_to_geojson(contours: &[ContourLine]) -> String {
+    let mut features = vec![];
+
+    for contour in contours {
+        for polyline in &contour.polylines {
+            let mut points = polyline.points.clone();
+
+            if polyline.closed {
+                points.extend(polyline.points.first());
+            }
+
+            let coordinates: Vec<String> = points
+                .iter()
+                .map(|(x, y)| format!("[{},{}]", x, y))
+                .collect();
+
+            features.push(format!(
+                r#"{{"type":"Feature","properties":{{"level":{},"major":{}}},"geometry":{{"type":"LineString","coordinates":[{}]}}}}"#,
+                contour.level,
+                contour.major,
+                coordinates.join(",")
+            ));
+        }
+    }
+
+    format!(
+        r#"{{"type":"FeatureCollection","features":[{}]}}"#,
+        features.join(",")
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::map::MapMonad;
+
+    fn hill() -> Map<f64> {
+        Map::return_single(0.0, 20, 20).and_then_with_coordinates(|_, x, y| {
+            let (dx, dy) = (x as f64 - 10.0, y as f64 - 10.0);
+
+            1.0 - (dx * dx + dy * dy).sqrt() / 10.0
+        })
+    }
+
+    #[test]
+    fn test_contours_around_a_hill_are_closed() {
+        let polylines = trace_contours(&hill(), 0.5);
+
+        assert_eq!(polylines.len(), 1);
+        assert!(polylines[0].closed);
+        // every point is 5 cells from the top of the hill
+        for (x, y) in &polylines[0].points {
+            let distance = ((x - 10.5).powi(2) + (y - 10.5).powi(2)).sqrt();
+
+            assert!((distance - 5.0).abs() < 0.5);
+        }
+    }
+
+    #[test]
+    fn test_contours_split_where_they_wrap() {
+        // a hill on the western edge of the map
+        let hill = Map::return_single(0.0, 20, 20).and_then_with_coordinates(|_, x, y| {
+            let (dx, dy) = ((x as f64 + 10.0) % 20.0 - 10.0, y as f64 - 10.0);
+
+            1.0 - (dx * dx + dy * dy).sqrt() / 10.0
+        });
+        let polylines = trace_contours(&hill, 0.5);
+
+        assert_eq!(polylines.len(), 2);
+        assert!(polylines.iter().all(|polyline| !polyline.closed));
+        assert!(polylines
+            .iter()
+            .flat_map(|polyline| polyline.points.windows(2))
+            .all(|pair| (pair[0].0 - pair[1].0).abs() < 2.0));
+    }
+
+    #[test]
+    fn test_levels_above_and_below_sea_level() {
+        let generator = ContourGeneratorBuilder::new(0.2)
+            .sea_level(0.4)
+            .major_every(2)
+            .bathymetry(true)
+            .build();
+        let levels: Vec<(i32, bool)> = generator
+            .levels()
+            .iter()
+            .map(|(level, major)| ((level * 10.0).round() as i32, *major))
+            .collect();
+
+        assert_eq!(
+            levels,
+            vec![
+                (0, true),
+                (2, false),
+                (4, true),
+                (6, false),
+                (8, true),
+                (10, false)
+            ]
+        );
+        assert!(contours_to_geojson(&generator.generate(&hill())).starts_with('{'));
+    }
+}
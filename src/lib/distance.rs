@@ -0,0 +1,244 @@
+//! This module provides Euclidean distance transforms over
+//! boolean [`Map`]s.
+//!
+//! Distances are measured in cells and wrap around the
+//! east-west edges of the map, matching the wrapping of the
+//! noise generators. The transform is computed in two passes,
+//! first down every column and then along every row, with each
+//! pass running concurrently.
+//!
+//! This module provides the following functions:
+//! - [`distance_transform`]
+//! - [`distance_to_water`]
+//! - [`signed_distance_to_coast`]
+//!
+//! # Examples
+//!
+//! ```
+//! use ficture::distance::signed_distance_to_coast;
+//! use ficture::map::{Map, MapMonad};
+//!
+//! let elevation = Map::return_single(0.0, 10, 10);
+//! let elevation = elevation.and_then_with_coordinates(|_, x, _| x as f64 / 10.0);
+//! let land = elevation.and_then(|elevation| elevation >= 0.5);
+//! let distance = signed_distance_to_coast(&land);
+//! ```
+use rayon::prelude::*;
+
+use crate::map::Map;
+
+/// A stand in for infinity that keeps the arithmetic in
+/// [`squared_distance_1d`] finite.
+const FAR: f64 = 1e20;
+
+/// Computes the squared distance from every point of `f` to the
+/// nearest point with a value of 0, using the lower envelope of
+/// parabolas described by Felzenszwalb and Huttenlocher.
+fn squared_distance_1d(f: &[f64]) -> Vec<f64> {
+    if f.is_empty() {
+        return Vec::new();
+    }
+
+    let n = f.len();
+    let mut distances = vec![0.0; n];
+    let mut vertices = vec![0; n];
+    let mut boundaries = vec![0.0; n + 1];
+    let mut k = 0;
+
+    boundaries[0] = f64::NEG_INFINITY;
+    boundaries[1] = f64::INFINITY;
+
+    for q in 1..n {
+        let intersection =
+            |v: usize| ((f[q] + (q * q) as f64) - (f[v] + (v * v) as f64)) / (2 * q - 2 * v) as f64;
+        let mut s = intersection(vertices[k]);
+
+        while s <= boundaries[k] {
+            k -= 1;
+            s = intersection(vertices[k]);
+        }
+
+        k += 1;
+        vertices[k] = q;
+        boundaries[k] = s;
+        boundaries[k + 1] = f64::INFINITY;
+    }
+
+    k = 0;
+
+    for (q, distance) in distances.iter_mut().enumerate() {
+        while boundaries[k + 1] < q as f64 {
+            k += 1;
+        }
+
+        let offset = q as f64 - vertices[k] as f64;
+        *distance = offset * offset + f[vertices[k]];
+    }
+
+    distances
+}
+
+/// Computes the Euclidean distance from every cell of a map to the
+/// nearest cell that is `true` in `mask`. Cells that are `true`
+/// have a distance of 0. If no cell is `true`, every distance is
+/// infinite.
+pub fn distance_transform(mask: &Map<bool>) -> Map<f64> {
+    let (width, height) = (mask.width(), mask.height());
+
+    // the first pass runs down each column, which does not wrap
+    let columns: Vec<Vec<f64>> = (0..width)
+        .into_par_iter()
+        .map(|x| {
+            let f: Vec<f64> = (0..height)
+                .map(|y| if *mask.get(x, y) { 0.0 } else { FAR })
+                .collect();
+
+            squared_distance_1d(&f)
+        })
+        .collect();
+
+    // the second pass runs along each row. to wrap around the
+    // east-west edges, the row is repeated on either side of
+    // itself and only the middle copy is kept
+    let rows: Vec<Vec<f64>> = (0..height)
+        .into_par_iter()
+        .map(|y| {
+            let f: Vec<f64> = (0..3 * width).map(|x| columns[x % width][y]).collect();
+
+            squared_distance_1d(&f)[width..2 * width]
+                .iter()
+                .map(|distance| {
+                    if *distance >= FAR {
+                        f64::INFINITY
+                    } else {
+                        distance.sqrt()
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    Map::from_vec(rows.into_iter().flatten().collect(), width, height)
+}
+
+/// Computes the distance from every cell to the nearest water
+/// cell given a map of which cells are land. Water cells have a
+/// distance of 0.
+pub fn distance_to_water(land: &Map<bool>) -> Map<f64> {
+    let water = Map::from_vec(
+        land.iter().map(|is_land| !is_land).collect(),
+        land.width(),
+        land.height(),
+    );
+
+    distance_transform(&water)
+}
+
+/// Computes the signed distance from every cell to the coastline
+/// given a map of which cells are land. Land cells have a positive
+/// distance to the nearest water cell and water cells have a
+/// negative distance to the nearest land cell.
+pub fn signed_distance_to_coast(land: &Map<bool>) -> Map<f64> {
+    let to_land = distance_transform(land);
+    let to_water = distance_to_water(land);
+    let signed: Vec<f64> = to_land
+        .iter()
+        .zip(to_water.iter())
+        .map(|(to_land, to_water)| to_water - to_land)
+        .collect();
+
+    Map::from_vec(signed, land.width(), land.height())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{map::MapMonad, utils::wrap};
+
+    fn brute_force(mask: &Map<bool>) -> Map<f64> {
+        let (width, height) = (mask.width(), mask.height());
+        let mut distances = vec![];
+
+        for y in 0..height {
+            for x in 0..width {
+                let mut best = f64::INFINITY;
+
+                for other_y in 0..height {
+                    for other_x in 0..width {
+                        if *mask.get(other_x, other_y) {
+                            let dx = (x as isize - other_x as isize).unsigned_abs();
+                            let dx = dx.min(width - dx) as f64;
+                            let dy = y as f64 - other_y as f64;
+
+                            best = best.min((dx * dx + dy * dy).sqrt());
+                        }
+                    }
+                }
+
+                distances.push(best);
+            }
+        }
+
+        Map::from_vec(distances, width, height)
+    }
+
+    #[test]
+    fn test_distance_transform_matches_brute_force() {
+        let (width, height) = (23, 17);
+        let mask = Map::from_vec(
+            (0..width * height)
+                .map(|index| (index * 7919) % 31 == 0)
+                .collect(),
+            width,
+            height,
+        );
+        let expected = brute_force(&mask);
+        let actual = distance_transform(&mask);
+
+        for (expected, actual) in expected.iter().zip(actual.iter()) {
+            assert!((expected - actual).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_distance_transform_wraps_east_west() {
+        let (width, height) = (20, 5);
+        let mask = Map::from_vec(
+            (0..width * height)
+                .map(|index| index % width == 0)
+                .collect(),
+            width,
+            height,
+        );
+        let distance = distance_transform(&mask);
+
+        assert_eq!(*distance.get(wrap(-1, width), 2), 1.0);
+        assert_eq!(*distance.get(10, 2), 10.0);
+    }
+
+    #[test]
+    fn test_empty_rows_have_no_distances() {
+        assert!(squared_distance_1d(&[]).is_empty());
+        assert!(distance_transform(&Map::from_vec(vec![], 0, 4)).is_empty());
+        assert!(distance_transform(&Map::from_vec(vec![], 4, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_signed_distance_to_coast() {
+        let (width, height) = (10, 10);
+        let land = Map::from_vec(
+            (0..width * height)
+                .map(|index| index % width >= 5)
+                .collect(),
+            width,
+            height,
+        );
+        let distance = signed_distance_to_coast(&land);
+
+        assert!(*distance.get(7, 0) > 0.0);
+        assert!(*distance.get(2, 0) < 0.0);
+        assert!(distance_transform(&Map::return_single(false, 3, 3))
+            .iter()
+            .all(|distance| distance.is_infinite()));
+    }
+}
@@ -0,0 +1,381 @@
+//! This module provides a [`FactorEvaluator`] for choosing biomes
+//! and colors from any number of named factors.
+//!
+//! Where the [`ColorEvaluator`](crate::color::ColorEvaluator) is
+//! limited to elevation and moisture, a factor map declares its own
+//! axes, such as temperature, moisture and elevation. A biome is
+//! chosen either by splitting the factors into nested ranges or by
+//! finding the biome whose centroid is nearest, and its color is
+//! taken from its gradient at the value of one chosen factor.
+//!
+//! Cells are read through the [`Channels`] trait, so a factor map
+//! can be evaluated over a [`Cell`](crate::cell::Cell) or over a
+//! [`NamedCell`](crate::cell::NamedCell) with arbitrary channels.
+//!
+//! # Examples
+//!
+//! ```
+//! use ficture::cell::NamedCell;
+//! use ficture::config::Config;
+//!
+//! let config: Config = serde_yaml::from_str(r##"
+//! biomes: {}
+//! noise_generators: {}
+//! biome_maps: {}
+//! factor_maps:
+//!   climate:
+//!     axes: [temperature, moisture]
+//!     gradient_factor: moisture
+//!     rule: nearest
+//!     biomes:
+//!       - name: desert
+//!         centroid: { temperature: 0.9, moisture: 0.1 }
+//!         gradient: ["#a6926c", "#827356"]
+//!       - name: tundra
+//!         centroid: { temperature: 0.1, moisture: 0.5 }
+//!         gradient: ["#adad9c", "#d1cfba"]
+//! "##).unwrap();
+//!
+//! let evaluator = config.get_factor_evaluator("climate").unwrap();
+//! let cell = NamedCell::new().with("temperature", 0.8).with("moisture", 0.2);
+//!
+//! assert_eq!(evaluator.biome(evaluator.classify(&cell)).unwrap().name, "desert");
+//! ```
+use image::Rgba;
+
+use crate::{
+    biome::BiomeId,
+    cell::Channels,
+    color::{get_color_func, ColorFunc, ColorResult},
+    config::{FactorMap, FactorRule, FactorSplit},
+    utils::normalize,
+};
+
+/// The identifier and name of a biome in a factor map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedBiome {
+    pub id: BiomeId,
+    pub name: String,
+}
+
+/// A factor split into ranges.
+struct Split {
+    factor: String,
+    ranges: Vec<Range>,
+}
+
+/// A single normalized range of a factor.
+struct Range {
+    lower: f64,
+    upper: f64,
+    node: Node,
+}
+
+/// What a range of a factor contains.
+enum Node {
+    Split(Split),
+    Biome(usize),
+    /// A range without a biome, which cells falling into it don't
+    /// belong to.
+    Empty,
+}
+
+/// The center of a biome chosen by the nearest centroid rule.
+struct Centroid {
+    values: Vec<(String, f64)>,
+    biome: usize,
+}
+
+/// The rule used to choose a biome.
+enum Rule {
+    Nested(Split),
+    Nearest(Vec<Centroid>),
+}
+
+impl Split {
+    /// Creates a [`Split`] from a config file, numbering the biomes
+    /// within it depth first starting from `next_biome`.
+    fn from_config(split: &FactorSplit, next_biome: &mut usize) -> Self {
+        let total_size = split.total_size();
+        let mut cumulative_size = 0.0;
+        let mut ranges = vec![];
+
+        for range in &split.ranges {
+            let lower = cumulative_size / total_size;
+            // only ranges with a biome take an index, so that the
+            // indices stay in step with the biomes of the factor map
+            let node = match (&range.split, &range.biome) {
+                (Some(inner), _) => Node::Split(Split::from_config(inner, next_biome)),
+                (None, Some(_)) => {
+                    *next_biome += 1;
+                    Node::Biome(*next_biome - 1)
+                }
+                (None, None) => Node::Empty,
+            };
+
+            cumulative_size += range.size;
+            ranges.push(Range {
+                lower,
+                upper: cumulative_size / total_size,
+                node,
+            });
+        }
+
+        Self {
+            factor: split.factor.clone(),
+            ranges,
+        }
+    }
+}
+
+/// A structure for choosing biomes and colors based on any number
+/// of named factors.
+pub struct FactorEvaluator {
+    gradient_factor: String,
+    rule: Rule,
+    biomes: Vec<NamedBiome>,
+    colors: Vec<ColorFunc>,
+}
+
+impl FactorEvaluator {
+    /// Creates a [`FactorEvaluator`] from a factor map loaded from
+    /// a config file.
+    pub(crate) fn from_factor_map(factor_map: &FactorMap) -> ColorResult<Self> {
+        let config_biomes = factor_map.biomes();
+        let mut biomes = vec![];
+        let mut colors = vec![];
+
+        for (biome, id) in config_biomes.iter().zip(factor_map.biome_ids()) {
+            biomes.push(NamedBiome {
+                id,
+                name: biome
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| format!("biome_{}", id.0)),
+            });
+            colors.push(get_color_func(&biome.gradient)?);
+        }
+
+        let rule = match &factor_map.rule {
+            FactorRule::Nested { split } => Rule::Nested(Split::from_config(split, &mut 0)),
+            FactorRule::Nearest { biomes } => Rule::Nearest(
+                biomes
+                    .iter()
+                    .enumerate()
+                    .map(|(index, biome)| Centroid {
+                        values: biome
+                            .centroid
+                            .iter()
+                            .map(|(axis, value)| (axis.clone(), *value))
+                            .collect(),
+                        biome: index,
+                    })
+                    .collect(),
+            ),
+        };
+
+        Ok(Self {
+            gradient_factor: factor_map.gradient_factor.clone(),
+            rule,
+            biomes,
+            colors,
+        })
+    }
+
+    /// Gets the index of the biome that a cell belongs to, along
+    /// with the position of the cell within that biome's gradient.
+    fn select(&self, cell: &impl Channels) -> Option<(usize, f64)> {
+        let gradient_value = cell.channel(&self.gradient_factor)?;
+
+        match &self.rule {
+            Rule::Nested(split) => {
+                let mut split = split;
+                let mut bounds = (0.0, 1.0);
+
+                loop {
+                    let value = cell.channel(&split.factor)?;
+                    let index = split.ranges.partition_point(|range| range.upper < value);
+                    let range = split.ranges.get(index)?;
+
+                    // when the gradient factor is split, the gradient
+                    // spans only the range that the cell falls within
+                    if split.factor == self.gradient_factor {
+                        bounds = (range.lower, range.upper);
+                    }
+
+                    match &range.node {
+                        Node::Split(inner) => split = inner,
+                        Node::Biome(biome) => {
+                            let position = normalize(gradient_value, bounds.0, bounds.1);
+
+                            return Some((*biome, position.clamp(0.0, 1.0)));
+                        }
+                        Node::Empty => return None,
+                    }
+                }
+            }
+            Rule::Nearest(centroids) => {
+                let mut nearest = None;
+
+                for centroid in centroids {
+                    let mut distance = 0.0;
+
+                    for (axis, center) in &centroid.values {
+                        let offset = cell.channel(axis)? - center;
+                        distance += offset * offset;
+                    }
+
+                    match nearest {
+                        Some((_, nearest_distance)) if nearest_distance <= distance => {}
+                        _ => nearest = Some((centroid.biome, distance)),
+                    }
+                }
+
+                nearest.map(|(biome, _)| (biome, gradient_value.clamp(0.0, 1.0)))
+            }
+        }
+    }
+
+    /// Gets the biome that a cell belongs to. Returns
+    /// [`BiomeId::NONE`] if the cell is missing one of the factors
+    /// or falls outside of the factor map.
+    pub fn classify(&self, cell: &impl Channels) -> BiomeId {
+        self.select(cell)
+            .map(|(biome, _)| self.biomes[biome].id)
+            .unwrap_or(BiomeId::NONE)
+    }
+
+    /// Gets the color of a cell. Cells that don't belong to a biome
+    /// are transparent.
+    pub fn evaluate(&self, cell: &impl Channels) -> Rgba<u8> {
+        match self.select(cell) {
+            Some((biome, position)) => (self.colors[biome])(position),
+            None => Rgba([0, 0, 0, 0]),
+        }
+    }
+
+    /// Gets every biome in the factor map, in the order that they
+    /// are declared in the config file.
+    pub fn biomes(&self) -> &[NamedBiome] {
+        &self.biomes
+    }
+
+    /// Gets the biome with the given identifier.
+    pub fn biome(&self, id: BiomeId) -> Option<&NamedBiome> {
+        self.biomes.iter().find(|biome| biome.id == id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cell::{Cell, NamedCell};
+
+    fn factor_map(yaml: &str) -> FactorMap {
+        let factor_map: FactorMap = serde_yaml::from_str(yaml).unwrap();
+
+        factor_map.validate().unwrap();
+        factor_map
+    }
+
+    #[test]
+    fn test_nested_three_factors() {
+        let factor_map = factor_map(
+            r##"
+            axes: [temperature, moisture, elevation]
+            gradient_factor: elevation
+            rule: nested
+            split:
+              factor: temperature
+              ranges:
+                - size: 0.5
+                  biome: { name: tundra, gradient: ["#000000", "#ffffff"] }
+                - size: 0.5
+                  split:
+                    factor: moisture
+                    ranges:
+                      - size: 0.5
+                        biome: { name: desert, gradient: ["#000000"] }
+                      - size: 0.5
+                        split:
+                          factor: elevation
+                          ranges:
+                            - size: 0.5
+                              biome: { name: forest, id: 9, gradient: ["#000000", "#ffffff"] }
+                            - size: 0.5
+                              biome: { name: mountain, gradient: ["#ffffff"] }
+            "##,
+        );
+        let evaluator = FactorEvaluator::from_factor_map(&factor_map).unwrap();
+        let cell = |temperature, moisture, elevation| {
+            NamedCell::new()
+                .with("temperature", temperature)
+                .with("moisture", moisture)
+                .with("elevation", elevation)
+        };
+
+        assert_eq!(evaluator.classify(&cell(0.2, 0.9, 0.1)), BiomeId(1));
+        assert_eq!(evaluator.classify(&cell(0.7, 0.2, 0.1)), BiomeId(2));
+        assert_eq!(evaluator.classify(&cell(0.7, 0.7, 0.2)), BiomeId(9));
+        assert_eq!(evaluator.classify(&cell(0.7, 0.7, 0.8)), BiomeId(4));
+        // the gradient spans the elevation range of the forest
+        assert_eq!(
+            evaluator.evaluate(&cell(0.7, 0.7, 0.5)),
+            Rgba([255, 255, 255, 255])
+        );
+        // the gradient spans all elevation for the tundra
+        assert_eq!(
+            evaluator.evaluate(&cell(0.2, 0.7, 0.0)),
+            Rgba([0, 0, 0, 255])
+        );
+        // cells without the factors don't belong to a biome
+        assert_eq!(
+            evaluator.classify(&Cell {
+                elevation: 0.5,
+                moisture: 0.5
+            }),
+            BiomeId::NONE
+        );
+    }
+
+    #[test]
+    fn test_range_without_biome_is_skipped() {
+        let factor_map: FactorMap = serde_yaml::from_str(
+            r##"
+            axes: [temperature]
+            gradient_factor: temperature
+            rule: nested
+            split:
+              factor: temperature
+              ranges:
+                - size: 0.5
+                - size: 0.5
+                  biome: { name: desert, gradient: ["#000000"] }
+            "##,
+        )
+        .unwrap();
+        let evaluator = FactorEvaluator::from_factor_map(&factor_map).unwrap();
+        let cell = |temperature| NamedCell::new().with("temperature", temperature);
+
+        assert!(factor_map.validate().is_ok());
+        assert_eq!(evaluator.classify(&cell(0.2)), BiomeId::NONE);
+        assert_eq!(evaluator.classify(&cell(0.8)), BiomeId(1));
+    }
+
+    #[test]
+    fn test_unknown_axis_is_invalid() {
+        let factor_map: FactorMap = serde_yaml::from_str(
+            r##"
+            axes: [temperature]
+            gradient_factor: elevation
+            rule: nearest
+            biomes:
+              - centroid: { temperature: 0.5 }
+                gradient: ["#000000"]
+            "##,
+        )
+        .unwrap();
+
+        assert!(factor_map.validate().is_err());
+    }
+}
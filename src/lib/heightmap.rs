@@ -0,0 +1,183 @@
+//! This module provides a [`HeightmapExporter`] for turning layers
+//! of values, such as elevation, into grayscale heightmaps that can
+//! be imported into other tools.
+//!
+//! Heightmaps are 16 bits per pixel by default so that they keep as
+//! much of the precision of the map as possible, with 8 bits per
+//! pixel available for tools that don't support 16 bit images.
+//!
+//! # Examples
+//!
+//! ```
+//! use ficture::heightmap::{BitDepth, HeightmapExporterBuilder};
+//! use ficture::map::{Map, MapMonad};
+//!
+//! let elevation = Map::return_single(0.0, 10, 10);
+//! let elevation = elevation.and_then_with_coordinates(|_, x, _| x as f64 / 10.0);
+//!
+//! let exporter = HeightmapExporterBuilder::new()
+//!     .range(0.0, 1.0)
+//!     .bit_depth(BitDepth::Sixteen)
+//!     .build();
+//! let image = exporter.to_image(&elevation);
+//!
+//! assert_eq!(image.width(), 10);
+//! ```
+use std::path::Path;
+
+use image::{DynamicImage, GrayImage, ImageBuffer, ImageResult, Luma};
+
+use crate::{map::Map, utils::normalize};
+
+/// The number of bits used for each pixel of a heightmap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitDepth {
+    /// 256 levels of height.
+    Eight,
+    /// 65536 levels of height.
+    Sixteen,
+}
+
+/// A structure for exporting layers of values as grayscale
+/// heightmaps.
+pub struct HeightmapExporter {
+    min: f64,
+    max: f64,
+    sea_level: f64,
+    bit_depth: BitDepth,
+}
+
+impl HeightmapExporter {
+    /// Gets the height of a value from 0-1. Values are measured from
+    /// the sea level, then the range of the exporter is stretched
+    /// over the full range of heights, clamping anything outside of
+    /// it.
+    pub fn height(&self, value: f64) -> f64 {
+        normalize(value - self.sea_level, self.min, self.max).clamp(0.0, 1.0)
+    }
+
+    /// Converts a map into a 16 bit grayscale image.
+    pub fn to_image16(&self, map: &Map<f64>) -> ImageBuffer<Luma<u16>, Vec<u16>> {
+        ImageBuffer::from_fn(map.width() as u32, map.height() as u32, |x, y| {
+            let height = self.height(*map.get(x as usize, y as usize));
+
+            Luma([(height * u16::MAX as f64).round() as u16])
+        })
+    }
+
+    /// Converts a map into an 8 bit grayscale image.
+    pub fn to_image8(&self, map: &Map<f64>) -> GrayImage {
+        ImageBuffer::from_fn(map.width() as u32, map.height() as u32, |x, y| {
+            let height = self.height(*map.get(x as usize, y as usize));
+
+            Luma([(height * u8::MAX as f64).round() as u8])
+        })
+    }
+
+    /// Converts a map into a grayscale image with the bit depth of
+    /// the exporter.
+    pub fn to_image(&self, map: &Map<f64>) -> DynamicImage {
+        match self.bit_depth {
+            BitDepth::Eight => DynamicImage::ImageLuma8(self.to_image8(map)),
+            BitDepth::Sixteen => DynamicImage::ImageLuma16(self.to_image16(map)),
+        }
+    }
+
+    /// Saves a map as a grayscale image, with the format determined
+    /// by the extension of the path. Use PNG to keep all 16 bits.
+    pub fn save(&self, map: &Map<f64>, path: impl AsRef<Path>) -> ImageResult<()> {
+        self.to_image(map).save(path)
+    }
+}
+
+/// A builder for the [`HeightmapExporter`].
+pub struct HeightmapExporterBuilder {
+    min: f64,
+    max: f64,
+    sea_level: f64,
+    bit_depth: BitDepth,
+}
+
+impl HeightmapExporterBuilder {
+    /// Creates the [`HeightmapExporterBuilder`].
+    pub fn new() -> Self {
+        Self {
+            min: 0.0,
+            max: 1.0,
+            sea_level: 0.0,
+            bit_depth: BitDepth::Sixteen,
+        }
+    }
+
+    /// Sets the range of values, measured from the sea level, that
+    /// is mapped to black and white.
+    pub fn range(mut self, min: f64, max: f64) -> Self {
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    /// Sets the sea level that values are measured from. With a
+    /// range starting at 0, everything below the sea is black.
+    pub fn sea_level(mut self, sea_level: f64) -> Self {
+        self.sea_level = sea_level;
+        self
+    }
+
+    /// Sets the number of bits used for each pixel.
+    pub fn bit_depth(mut self, bit_depth: BitDepth) -> Self {
+        self.bit_depth = bit_depth;
+        self
+    }
+
+    /// Construct the [`HeightmapExporter`] based on the
+    /// defined attributes.
+    pub fn build(self) -> HeightmapExporter {
+        HeightmapExporter {
+            min: self.min,
+            max: self.max,
+            sea_level: self.sea_level,
+            bit_depth: self.bit_depth,
+        }
+    }
+}
+
+impl Default for HeightmapExporterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_heightmap_keeps_precision() {
+        let map = Map::from_vec(vec![0.0, 0.5, 1.0, 0.5 + 1.0 / 65535.0], 4, 1);
+        let exporter = HeightmapExporterBuilder::new().build();
+        let image = exporter.to_image16(&map);
+        let image8 = exporter.to_image8(&map);
+
+        assert_eq!(image.get_pixel(0, 0).0[0], 0);
+        assert_eq!(image.get_pixel(2, 0).0[0], u16::MAX);
+        assert_eq!(image.get_pixel(3, 0).0[0], image.get_pixel(1, 0).0[0] + 1);
+        assert_eq!(image8.get_pixel(1, 0).0[0], image8.get_pixel(3, 0).0[0]);
+    }
+
+    #[test]
+    fn test_heightmap_measures_from_sea_level() {
+        let map = Map::from_vec(vec![0.1, 0.3, 0.55, 0.8], 4, 1);
+        let exporter = HeightmapExporterBuilder::new()
+            .sea_level(0.3)
+            .range(0.0, 0.5)
+            .bit_depth(BitDepth::Eight)
+            .build();
+        let image = exporter.to_image(&map).to_luma8();
+
+        assert_eq!(image.get_pixel(0, 0).0[0], 0);
+        assert_eq!(image.get_pixel(1, 0).0[0], 0);
+        assert_eq!(image.get_pixel(2, 0).0[0], 128);
+        assert_eq!(image.get_pixel(3, 0).0[0], 255);
+    }
+}
@@ -3,6 +3,9 @@
 //!
 //! This module provides the following helper functions:
 //! - [`pixel_map_to_image`]
+//! - [`pixel_map_to_rgba_image`]
+//! - [`value_map_to_image`]
+//! - [`biome_map_to_image`]
 //!
 //! # Examples
 //!
@@ -17,7 +20,9 @@
 //! let map = map.and_then(|cell| Rgb([0, 0, 0]));
 //! let image = map.extract(pixel_map_to_image);
 //! ```
-use image::{Rgb, RgbImage};
+use image::{GrayImage, ImageBuffer, Luma, Rgb, RgbImage, Rgba, RgbaImage};
+
+use crate::biome::BiomeId;
 
 /// A helper function for use in `extract` to turn a [`Map`](crate::map::Map) of
 /// `Rgb<u8>` into an RGB image.
@@ -34,6 +39,59 @@ pub fn pixel_map_to_image(pixels: Vec<Rgb<u8>>, width: usize, height: usize) ->
     image
 }
 
+/// A helper function for use in `extract` to turn a [`Map`](crate::map::Map) of
+/// `Rgba<u8>` into an RGBA image, keeping the transparency of each pixel.
+pub fn pixel_map_to_rgba_image(pixels: Vec<Rgba<u8>>, width: usize, height: usize) -> RgbaImage {
+    let mut pixel_iter = pixels.iter();
+    let mut image = RgbaImage::new(width as u32, height as u32);
+
+    for y in 0..height {
+        for x in 0..width {
+            image.put_pixel(x as u32, y as u32, *pixel_iter.next().unwrap());
+        }
+    }
+
+    image
+}
+
+/// A helper function for use in `extract` to turn a [`Map`](crate::map::Map) of
+/// values from 0-1, such as a hillshade, into a grayscale image. Values outside
+/// of 0-1 are clamped.
+pub fn value_map_to_image(values: Vec<f64>, width: usize, height: usize) -> GrayImage {
+    let mut value_iter = values.iter();
+    let mut image = GrayImage::new(width as u32, height as u32);
+
+    for y in 0..height {
+        for x in 0..width {
+            let value = value_iter.next().unwrap().clamp(0.0, 1.0);
+
+            image.put_pixel(x as u32, y as u32, Luma([(value * 255.0).round() as u8]));
+        }
+    }
+
+    image
+}
+
+/// A helper function for use in `extract` to turn a [`Map`](crate::map::Map) of
+/// [`BiomeId`]s into a 16 bit grayscale image, where the value of each pixel is
+/// the identifier of its biome.
+pub fn biome_map_to_image(
+    ids: Vec<BiomeId>,
+    width: usize,
+    height: usize,
+) -> ImageBuffer<Luma<u16>, Vec<u16>> {
+    let mut id_iter = ids.iter();
+    let mut image = ImageBuffer::new(width as u32, height as u32);
+
+    for y in 0..height {
+        for x in 0..width {
+            image.put_pixel(x as u32, y as u32, Luma([id_iter.next().unwrap().0]));
+        }
+    }
+
+    image
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -60,4 +118,13 @@ mod test {
         assert_eq!(image.width(), width as u32);
         assert_eq!(image.height(), height as u32);
     }
+
+    #[test]
+    fn test_rgba_image_keeps_transparency() {
+        let map = Map::from_vec(vec![Rgba([0, 0, 0, 0]), Rgba([255, 0, 0, 255])], 2, 1);
+        let image = map.extract(pixel_map_to_rgba_image);
+
+        assert_eq!(image.get_pixel(0, 0).0[3], 0);
+        assert_eq!(image.get_pixel(1, 0).0[3], 255);
+    }
 }
@@ -0,0 +1,310 @@
+//! This module provides connected-component labeling for finding
+//! the landmasses on a map, along with steps for reshaping the
+//! landmasses that a map ends up with.
+//!
+//! Cells are connected to the cells directly north, south, east
+//! and west of them, and landmasses wrap around the east-west
+//! edges of the map.
+//!
+//! # Examples
+//!
+//! ```
+//! use ficture::landmass::{label_landmasses, remove_small_islands};
+//! use ficture::map::{Map, MapMonad};
+//!
+//! let elevation = Map::return_single(0.0, 10, 10);
+//! let elevation = elevation.and_then_with_coordinates(|_, x, y| {
+//!     if x > 5 || (x, y) == (1, 1) { 1.0 } else { 0.0 }
+//! });
+//!
+//! // sink the single cell island
+//! let elevation = remove_small_islands(elevation, 0.5, 2);
+//! let landmasses = label_landmasses(&elevation, 0.5);
+//!
+//! assert_eq!(landmasses.landmasses.len(), 1);
+//! ```
+use std::collections::VecDeque;
+
+use crate::{map::Map, utils::wrap};
+
+/// The bounds of a landmass. Since landmasses wrap around the
+/// east-west edges of the map, `min_x + width` may be greater
+/// than the width of the map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundingBox {
+    pub min_x: usize,
+    pub min_y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Information about a single landmass.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Landmass {
+    /// The label given to the cells of this landmass.
+    pub id: usize,
+    /// The number of cells in the landmass.
+    pub area: usize,
+    /// The bounds of the landmass.
+    pub bounds: BoundingBox,
+    /// The average x and y coordinates of the cells in the
+    /// landmass.
+    pub centroid: (f64, f64),
+    /// The x and y coordinates of the highest cell in the
+    /// landmass.
+    pub highest_point: (usize, usize),
+    /// The elevation of the highest cell in the landmass.
+    pub highest_elevation: f64,
+    /// The number of cell edges shared between the landmass
+    /// and the water around it.
+    pub coastline_length: usize,
+}
+
+/// The result of labeling the landmasses on a map.
+pub struct Landmasses {
+    /// The label of the landmass each cell belongs to, or
+    /// `None` for water.
+    pub labels: Map<Option<usize>>,
+    /// The landmasses that were found, indexed by their label.
+    pub landmasses: Vec<Landmass>,
+}
+
+/// Finds every landmass on a map, treating cells at or above
+/// `sea_level` as land.
+pub fn label_landmasses(elevation: &Map<f64>, sea_level: f64) -> Landmasses {
+    let (width, height) = (elevation.width(), elevation.height());
+    let is_land = |x: usize, y: usize| *elevation.get(x, y) >= sea_level;
+    let mut labels: Vec<Option<usize>> = vec![None; width * height];
+    let mut landmasses = vec![];
+
+    for start_y in 0..height {
+        for start_x in 0..width {
+            if !is_land(start_x, start_y) || labels[start_y * width + start_x].is_some() {
+                continue;
+            }
+
+            let id = landmasses.len();
+            let mut area = 0;
+            let mut coastline_length = 0;
+            let (mut min_x, mut max_x) = (isize::MAX, isize::MIN);
+            let (mut min_y, mut max_y) = (usize::MAX, 0);
+            let (mut sum_x, mut sum_y) = (0.0, 0.0);
+            let mut highest_point = (start_x, start_y);
+            let mut highest_elevation = f64::MIN;
+
+            // cells are queued with their x coordinate unwrapped, so
+            // that a landmass crossing the east-west edges has
+            // contiguous coordinates
+            let mut queue = VecDeque::from([(start_x as isize, start_y)]);
+            labels[start_y * width + start_x] = Some(id);
+
+            while let Some((unwrapped_x, y)) = queue.pop_front() {
+                let x = wrap(unwrapped_x, width);
+                let cell_elevation = *elevation.get(x, y);
+
+                area += 1;
+                min_x = min_x.min(unwrapped_x);
+                max_x = max_x.max(unwrapped_x);
+                min_y = min_y.min(y);
+                max_y = max_y.max(y);
+                sum_x += unwrapped_x as f64;
+                sum_y += y as f64;
+
+                if cell_elevation > highest_elevation {
+                    highest_elevation = cell_elevation;
+                    highest_point = (x, y);
+                }
+
+                let mut neighbors = vec![(unwrapped_x - 1, y), (unwrapped_x + 1, y)];
+
+                if y > 0 {
+                    neighbors.push((unwrapped_x, y - 1));
+                }
+                if y + 1 < height {
+                    neighbors.push((unwrapped_x, y + 1));
+                }
+
+                for (neighbor_x, neighbor_y) in neighbors {
+                    let wrapped_x = wrap(neighbor_x, width);
+
+                    if !is_land(wrapped_x, neighbor_y) {
+                        coastline_length += 1;
+                    } else if labels[neighbor_y * width + wrapped_x].is_none() {
+                        labels[neighbor_y * width + wrapped_x] = Some(id);
+                        queue.push_back((neighbor_x, neighbor_y));
+                    }
+                }
+            }
+
+            landmasses.push(Landmass {
+                id,
+                area,
+                bounds: BoundingBox {
+                    min_x: wrap(min_x, width),
+                    min_y,
+                    width: ((max_x - min_x + 1) as usize).min(width),
+                    height: max_y - min_y + 1,
+                },
+                centroid: (
+                    (sum_x / area as f64).rem_euclid(width as f64),
+                    sum_y / area as f64,
+                ),
+                highest_point,
+                highest_elevation,
+                coastline_length,
+            });
+        }
+    }
+
+    Landmasses {
+        labels: Map::from_vec(labels, width, height),
+        landmasses,
+    }
+}
+
+/// A step that sinks every landmass smaller than `min_area` cells
+/// below `sea_level`. Sunken cells are mirrored about the sea level
+/// so that they are left as shallow water.
+pub fn remove_small_islands(elevation: Map<f64>, sea_level: f64, min_area: usize) -> Map<f64> {
+    let Landmasses { labels, landmasses } = label_landmasses(&elevation, sea_level);
+    let sunken: Vec<f64> = elevation
+        .iter()
+        .zip(labels.iter())
+        .map(|(elevation, label)| match label {
+            Some(id) if landmasses[*id].area < min_area => (2.0 * sea_level - elevation).max(0.0),
+            _ => *elevation,
+        })
+        .collect();
+
+    Map::from_vec(sunken, elevation.width(), elevation.height())
+}
+
+/// A step that guarantees that a map has at least `count` landmasses
+/// of at least `min_area` cells each. If the map has too few, the sea
+/// is raised until enough landmasses are separated from one another.
+/// Rather than changing the sea level, the elevation is then rescaled
+/// so that the raised sea level lands on `sea_level` and the elevation
+/// stays within 0-1. If no sea level separates enough landmasses, the
+/// map is returned unchanged.
+pub fn ensure_continents(
+    elevation: Map<f64>,
+    sea_level: f64,
+    count: usize,
+    min_area: usize,
+) -> Map<f64> {
+    let continents = |sea_level: f64| {
+        label_landmasses(&elevation, sea_level)
+            .landmasses
+            .iter()
+            .filter(|landmass| landmass.area >= min_area)
+            .count()
+    };
+
+    if continents(sea_level) >= count {
+        return elevation;
+    }
+
+    let steps = 100;
+    let raised_sea_level = (1..steps)
+        .map(|step| sea_level + (1.0 - sea_level) * step as f64 / steps as f64)
+        .find(|raised_sea_level| continents(*raised_sea_level) >= count);
+
+    match raised_sea_level {
+        Some(raised_sea_level) => {
+            let rescaled: Vec<f64> = elevation
+                .iter()
+                .map(|elevation| {
+                    if *elevation < raised_sea_level {
+                        elevation * sea_level / raised_sea_level
+                    } else {
+                        sea_level
+                            + (elevation - raised_sea_level) * (1.0 - sea_level)
+                                / (1.0 - raised_sea_level)
+                    }
+                })
+                .collect();
+
+            Map::from_vec(rescaled, elevation.width(), elevation.height())
+        }
+        None => elevation,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn map_from_rows(rows: &[&str]) -> Map<f64> {
+        let width = rows[0].len();
+        let cells = rows
+            .iter()
+            .flat_map(|row| {
+                row.chars().map(|c| match c {
+                    '#' => 1.0,
+                    '+' => 0.6,
+                    _ => 0.0,
+                })
+            })
+            .collect();
+
+        Map::from_vec(cells, width, rows.len())
+    }
+
+    #[test]
+    fn test_label_landmasses_wraps_east_west() {
+        let elevation = map_from_rows(&[
+            "#.......##", //
+            "#.........",
+            "....#.....",
+            "..........",
+        ]);
+        let Landmasses { labels, landmasses } = label_landmasses(&elevation, 0.5);
+
+        assert_eq!(landmasses.len(), 2);
+        assert_eq!(labels.get(0, 0), labels.get(9, 0));
+        assert_eq!(landmasses[0].area, 4);
+        assert_eq!(
+            landmasses[0].bounds,
+            BoundingBox {
+                min_x: 8,
+                min_y: 0,
+                width: 3,
+                height: 2,
+            }
+        );
+        assert_eq!(landmasses[1].area, 1);
+        assert_eq!(landmasses[1].coastline_length, 4);
+    }
+
+    #[test]
+    fn test_landmass_highest_point() {
+        let elevation = map_from_rows(&[
+            "..........", //
+            ".+++#++...",
+            "..........",
+        ]);
+        let landmasses = label_landmasses(&elevation, 0.5).landmasses;
+
+        assert_eq!(landmasses[0].highest_point, (4, 1));
+        assert_eq!(landmasses[0].centroid, (3.5, 1.0));
+    }
+
+    #[test]
+    fn test_ensure_continents_splits_landmasses() {
+        let elevation = map_from_rows(&[
+            "..........", //
+            ".###+###..",
+            "..........",
+        ]);
+
+        assert_eq!(label_landmasses(&elevation, 0.5).landmasses.len(), 1);
+
+        let elevation = ensure_continents(elevation, 0.5, 2, 3);
+        let landmasses = label_landmasses(&elevation, 0.5).landmasses;
+
+        assert_eq!(landmasses.len(), 2);
+        assert!(elevation
+            .iter()
+            .all(|elevation| (0.0..=1.0).contains(elevation)));
+    }
+}
@@ -0,0 +1,393 @@
+//! This module provides a [`Legend`] for rendering a labeled chart
+//! of a biome map to accompany generated maps.
+//!
+//! The legend draws the biome map as a chart with elevation along
+//! the x axis and moisture along the y axis. The thresholds between
+//! biomes are outlined and labeled along both axes, the ocean is
+//! drawn below the sea-level line, and a key lists the gradient and
+//! name of every biome.
+//!
+//! # Examples
+//!
+//! ```
+//! use ficture::config::Config;
+//! use ficture::legend::LegendBuilder;
+//!
+//! let config = Config::from_file("config/config.yaml").unwrap();
+//! let evaluator = config.get_color_evaluator("default").unwrap();
+//! let ocean = config.get_color_func("ocean").unwrap();
+//!
+//! let legend = LegendBuilder::new()
+//!     .sea_level(0.05)
+//!     .ocean(ocean)
+//!     .chart_size(128)
+//!     .build();
+//! let image = legend.render(&evaluator);
+//! ```
+use image::{Pixel, Rgba, RgbaImage};
+
+use crate::{
+    biome::BiomeId,
+    color::{ColorEvaluator, ColorFunc},
+    font::{draw_text, text_width, GLYPH_HEIGHT},
+    utils::normalize,
+};
+
+const BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const TEXT: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const BOUNDARY: Rgba<u8> = Rgba([0, 0, 0, 96]);
+const SEA_LEVEL: Rgba<u8> = Rgba([0, 64, 160, 255]);
+
+/// A structure for rendering a labeled legend of a biome map.
+pub struct Legend {
+    chart_size: u32,
+    font_scale: u32,
+    sea_level: f64,
+    ocean: Option<ColorFunc>,
+}
+
+impl Legend {
+    /// Blends a color over a pixel of an image, skipping pixels
+    /// outside of the image.
+    fn blend(image: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>) {
+        if (0..image.width() as i64).contains(&x) && (0..image.height() as i64).contains(&y) {
+            image.get_pixel_mut(x as u32, y as u32).blend(&color);
+        }
+    }
+
+    /// Gets the thresholds between the ranges in `bounds`, along
+    /// with both ends of the axis.
+    fn thresholds(bounds: impl Iterator<Item = f64>) -> Vec<f64> {
+        let mut thresholds: Vec<f64> = bounds.chain([0.0, 1.0]).collect();
+
+        thresholds.sort_by(|a, b| a.total_cmp(b));
+        thresholds.dedup_by(|a, b| (*a - *b).abs() < 1e-6);
+        thresholds
+    }
+
+    /// Gets the color of the chart at the given elevation and
+    /// moisture.
+    fn chart_color(&self, evaluator: &ColorEvaluator, elevation: f64, moisture: f64) -> Rgba<u8> {
+        match &self.ocean {
+            Some(ocean) if elevation < self.sea_level => {
+                ocean(normalize(elevation, 0.0, self.sea_level))
+            }
+            _ => evaluator.evaluate(elevation, moisture),
+        }
+    }
+
+    /// Gets the biome at the given elevation and moisture, treating
+    /// the ocean as having no biome.
+    fn chart_biome(&self, evaluator: &ColorEvaluator, elevation: f64, moisture: f64) -> BiomeId {
+        if self.ocean.is_some() && elevation < self.sea_level {
+            BiomeId::NONE
+        } else {
+            evaluator.classifier().classify(elevation, moisture)
+        }
+    }
+
+    /// Draws a horizontal strip of a gradient, from 0 at the left to
+    /// 1 at the right.
+    fn draw_swatch(
+        image: &mut RgbaImage,
+        x: i64,
+        y: i64,
+        width: u32,
+        height: u32,
+        color: impl Fn(f64) -> Rgba<u8>,
+    ) {
+        for dx in 0..width as i64 {
+            let color = color((dx as f64 + 0.5) / width as f64);
+
+            for dy in 0..height as i64 {
+                Self::blend(image, x + dx, y + dy, color);
+            }
+        }
+    }
+
+    /// Renders the legend of the biome map colored by `evaluator`.
+    pub fn render(&self, evaluator: &ColorEvaluator) -> RgbaImage {
+        let biomes = evaluator.classifier().biomes();
+        let scale = self.font_scale;
+        let size = self.chart_size as i64;
+        let margin = 8 * scale as i64;
+        let line_height = (GLYPH_HEIGHT + 4) as i64 * scale as i64;
+        let tick = 3 * scale as i64;
+
+        let elevation_thresholds = Self::thresholds(biomes.iter().map(|biome| biome.elevation.1));
+        let moisture_thresholds = Self::thresholds(biomes.iter().map(|biome| biome.moisture.1));
+        let format_threshold = |threshold: f64| format!("{threshold:.2}");
+
+        // the chart sits to the right of the moisture labels and
+        // below the title of the moisture axis
+        let label_width = text_width(&format_threshold(0.0), scale) as i64;
+        let chart_x = margin + label_width + tick + scale as i64;
+        let chart_y = margin + line_height;
+
+        // the key sits to the right of the chart
+        let swatch_width = 24 * scale;
+        let swatch_height = GLYPH_HEIGHT * scale;
+        let key_x = chart_x + size + 2 * margin;
+        let text_x = key_x + swatch_width as i64 + 2 * scale as i64;
+        let sea_level_label = format!("sea level ({})", format_threshold(self.sea_level));
+        let mut entries: Vec<String> = biomes.iter().map(|biome| biome.name.clone()).collect();
+
+        if self.ocean.is_some() {
+            entries.push(String::from("ocean"));
+        }
+        entries.push(sea_level_label.clone());
+
+        let key_width = entries
+            .iter()
+            .map(|entry| text_width(entry, scale) as i64)
+            .max()
+            .unwrap_or(0);
+        let width = text_x + key_width + margin;
+        let chart_bottom = chart_y + size + tick + 2 * line_height + margin;
+        let key_bottom = chart_y + entries.len() as i64 * line_height + margin;
+        let height = chart_bottom.max(key_bottom);
+        let mut image = RgbaImage::from_pixel(width as u32, height as u32, BACKGROUND);
+
+        // fill the chart, with moisture increasing upwards
+        let position = |pixel: i64| (pixel as f64 + 0.5) / size as f64;
+
+        for py in 0..size {
+            let moisture = 1.0 - position(py);
+
+            for px in 0..size {
+                let elevation = position(px);
+
+                Self::blend(
+                    &mut image,
+                    chart_x + px,
+                    chart_y + py,
+                    self.chart_color(evaluator, elevation, moisture),
+                );
+
+                // outline the thresholds between biomes
+                let biome = self.chart_biome(evaluator, elevation, moisture);
+                let east = self.chart_biome(evaluator, position(px + 1), moisture);
+                let south = self.chart_biome(evaluator, elevation, 1.0 - position(py + 1));
+
+                if biome != BiomeId::NONE
+                    && ((px + 1 < size && biome != east) || (py + 1 < size && biome != south))
+                {
+                    Self::blend(&mut image, chart_x + px, chart_y + py, BOUNDARY);
+                }
+            }
+        }
+
+        // mark the sea level with a dashed line
+        let sea_x = chart_x + (self.sea_level.clamp(0.0, 1.0) * size as f64).round() as i64;
+        let dash = 4 * scale as i64;
+
+        for py in 0..size {
+            if (py / dash) % 2 == 0 {
+                Self::blend(&mut image, sea_x, chart_y + py, SEA_LEVEL);
+            }
+        }
+
+        // label the elevation thresholds below the chart, skipping
+        // any labels that would overlap the previous one
+        let mut next_free_x = i64::MIN;
+
+        for threshold in &elevation_thresholds {
+            let x = chart_x + (threshold * (size - 1) as f64).round() as i64;
+
+            for dy in 0..tick {
+                Self::blend(&mut image, x, chart_y + size + dy, TEXT);
+            }
+
+            let label = format_threshold(*threshold);
+            let label_x = x - text_width(&label, scale) as i64 / 2;
+
+            if label_x >= next_free_x {
+                draw_text(
+                    &mut image,
+                    label_x,
+                    chart_y + size + tick + scale as i64,
+                    &label,
+                    scale,
+                    TEXT,
+                );
+                next_free_x = label_x + text_width(&label, scale) as i64 + 2 * scale as i64;
+            }
+        }
+
+        draw_text(
+            &mut image,
+            chart_x + (size - text_width("elevation", scale) as i64) / 2,
+            chart_y + size + tick + line_height + scale as i64,
+            "elevation",
+            scale,
+            TEXT,
+        );
+
+        // label the moisture thresholds to the left of the chart,
+        // from the bottom up
+        let mut next_free_y = i64::MAX;
+
+        for threshold in &moisture_thresholds {
+            let y = chart_y + ((1.0 - threshold) * (size - 1) as f64).round() as i64;
+
+            for dx in 1..=tick {
+                Self::blend(&mut image, chart_x - dx, y, TEXT);
+            }
+
+            let label = format_threshold(*threshold);
+            let label_y = y - (GLYPH_HEIGHT * scale) as i64 / 2;
+
+            if label_y + line_height <= next_free_y {
+                draw_text(&mut image, margin, label_y, &label, scale, TEXT);
+                next_free_y = label_y;
+            }
+        }
+
+        draw_text(&mut image, margin, margin, "moisture", scale, TEXT);
+
+        // list the gradient and name of every biome, followed by the
+        // ocean and the sea level
+        let mut entry_y = chart_y;
+
+        for biome in biomes {
+            let (lower, upper) = biome.elevation;
+
+            Self::draw_swatch(
+                &mut image,
+                key_x,
+                entry_y,
+                swatch_width,
+                swatch_height,
+                |t| evaluator.color(biome.id, lower + t * (upper - lower)),
+            );
+            draw_text(&mut image, text_x, entry_y, &biome.name, scale, TEXT);
+            entry_y += line_height;
+        }
+
+        if let Some(ocean) = &self.ocean {
+            Self::draw_swatch(
+                &mut image,
+                key_x,
+                entry_y,
+                swatch_width,
+                swatch_height,
+                ocean.as_ref(),
+            );
+            draw_text(&mut image, text_x, entry_y, "ocean", scale, TEXT);
+            entry_y += line_height;
+        }
+
+        for dx in 0..swatch_width as i64 {
+            if (dx / dash) % 2 == 0 {
+                Self::blend(
+                    &mut image,
+                    key_x + dx,
+                    entry_y + swatch_height as i64 / 2,
+                    SEA_LEVEL,
+                );
+            }
+        }
+        draw_text(&mut image, text_x, entry_y, &sea_level_label, scale, TEXT);
+
+        image
+    }
+}
+
+/// A builder for the [`Legend`].
+pub struct LegendBuilder {
+    chart_size: u32,
+    font_scale: u32,
+    sea_level: f64,
+    ocean: Option<ColorFunc>,
+}
+
+impl LegendBuilder {
+    /// Creates the [`LegendBuilder`].
+    pub fn new() -> Self {
+        Self {
+            chart_size: 256,
+            font_scale: 2,
+            sea_level: 0.0,
+            ocean: None,
+        }
+    }
+
+    /// Sets the width and height of the chart in pixels.
+    pub fn chart_size(mut self, chart_size: u32) -> Self {
+        self.chart_size = chart_size;
+        self
+    }
+
+    /// Sets how many pixels wide each pixel of the font is drawn.
+    pub fn font_scale(mut self, font_scale: u32) -> Self {
+        self.font_scale = font_scale;
+        self
+    }
+
+    /// Sets the elevation of the sea level.
+    pub fn sea_level(mut self, sea_level: f64) -> Self {
+        self.sea_level = sea_level;
+        self
+    }
+
+    /// Sets the gradient used to color the ocean, from the deepest
+    /// ocean to the sea level. Without an ocean gradient, the chart
+    /// is colored by the biome map all the way down.
+    pub fn ocean(mut self, ocean: ColorFunc) -> Self {
+        self.ocean = Some(ocean);
+        self
+    }
+
+    /// Construct the [`Legend`] based on the
+    /// defined attributes.
+    pub fn build(self) -> Legend {
+        Legend {
+            chart_size: self.chart_size.max(1),
+            font_scale: self.font_scale.max(1),
+            sea_level: self.sea_level,
+            ocean: self.ocean,
+        }
+    }
+}
+
+impl Default for LegendBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_legend_shows_ocean_below_sea_level() {
+        let config = Config::from_file("config/config.yaml").unwrap();
+        let evaluator = config.get_color_evaluator("default").unwrap();
+        let ocean = config.get_color_func("ocean").unwrap();
+        let legend = LegendBuilder::new()
+            .sea_level(0.5)
+            .ocean(ocean.clone())
+            .chart_size(100)
+            .build();
+        let image = legend.render(&evaluator);
+        // the chart starts after the margin, the moisture labels,
+        // the tick and a gap, and below the moisture title
+        let chart_x = 16 + text_width("0.00", 2) + 6 + 2;
+        let chart_y = 16 + 22;
+
+        assert!(image.width() > 100 && image.height() > 100);
+        assert_eq!(
+            *image.get_pixel(chart_x + 10, chart_y + 1),
+            ocean(normalize(10.5 / 100.0, 0.0, 0.5))
+        );
+    }
+
+    #[test]
+    fn test_thresholds_include_both_ends() {
+        let thresholds = Legend::thresholds([0.5, 1.0, 0.5, 0.25].into_iter());
+
+        assert_eq!(thresholds, vec![0.0, 0.25, 0.5, 1.0]);
+    }
+}
@@ -1,9 +1,30 @@
 //! This is a library used for generating world maps using
 //! noise.
+pub mod biome;
+pub mod bundle;
 pub mod cell;
+pub mod climate;
 pub mod color;
 pub mod config;
+pub mod contour;
+pub mod distance;
+pub mod factor;
+mod font;
+pub mod heightmap;
 pub mod image;
+pub mod landmass;
+pub mod legend;
 pub mod map;
+pub mod mask;
+pub mod mesh;
+pub mod metadata;
 pub mod noise;
+pub mod preview;
+pub mod raster;
+pub mod river;
+pub mod server;
+pub mod svg;
+pub mod tectonics;
+pub mod terrain;
+pub mod tiles;
 pub mod utils;
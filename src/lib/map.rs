@@ -41,9 +41,36 @@ impl<T> Map<T>
 where
     T: Send + Clone,
 {
+    /// Creates a [`Map`] from cells stored in row-major order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of cells does not match `width * height`.
+    pub fn from_vec(inner: Vec<T>, width: usize, height: usize) -> Self {
+        assert_eq!(
+            inner.len(),
+            width * height,
+            "expected {} cells for a {}x{} map",
+            width * height,
+            width,
+            height
+        );
+
+        Self {
+            width,
+            height,
+            inner,
+        }
+    }
+
+    /// Gets a reference to the cell at the coordinates `x` and `y`.
+    pub fn get(&self, x: usize, y: usize) -> &T {
+        &self.inner[y * self.width + x]
+    }
+
     /// Returns an iterator that iterates over the
     /// cells in a map.
-    pub fn iter(&self) -> MapIter<T> {
+    pub fn iter(&self) -> MapIter<'_, T> {
         MapIter {
             inner: &self.inner,
             index: 0,
@@ -0,0 +1,306 @@
+//! This module provides masks for shaping the elevation of a map
+//! into islands and continents that noise alone can't guarantee.
+//!
+//! Masks are generators of values from 0-1 and implement
+//! [`SimpleNoiseGenerator`], so they can be used anywhere a noise
+//! generator can. A [`MaskLayer`] combines a mask with a value,
+//! such as the elevation of a cell, using a [`BlendMode`].
+//!
+//! This module provides the following masks:
+//! - [`RadialFalloff`]
+//! - [`SquareFalloff`]
+//! - [`ImageMask`]
+//!
+//! # Examples
+//!
+//! ```
+//! use ficture::map::{Map, MapMonad};
+//! use ficture::mask::{BlendMode, MaskLayer, RadialFalloff};
+//!
+//! let layer = MaskLayer::new(
+//!     Box::new(RadialFalloff::new(10, 10, (0.5, 0.5), 1.0, 0.5)),
+//!     BlendMode::Multiply,
+//!     1.0,
+//! );
+//! let elevation = Map::return_single(1.0, 10, 10);
+//! let elevation = elevation.and_then_with_coordinates(|elevation, x, y| {
+//!     layer.apply(*elevation, x, y)
+//! });
+//! ```
+use image::{imageops::FilterType, DynamicImage, GrayImage, ImageResult};
+use serde::{Deserialize, Serialize};
+
+use crate::noise::SimpleNoiseGenerator;
+
+/// The ways that a mask can be combined with a value.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    /// Multiply the value by the mask.
+    Multiply,
+    /// Add the mask to the value.
+    Add,
+    /// Subtract the mask from the value.
+    Subtract,
+    /// Take the lesser of the value and the mask.
+    Min,
+    /// Take the greater of the value and the mask.
+    Max,
+    /// Replace the value with the mask.
+    Replace,
+}
+
+impl BlendMode {
+    /// Combines a value with a mask value.
+    pub fn blend(&self, value: f64, mask: f64) -> f64 {
+        match self {
+            BlendMode::Multiply => value * mask,
+            BlendMode::Add => value + mask,
+            BlendMode::Subtract => value - mask,
+            BlendMode::Min => value.min(mask),
+            BlendMode::Max => value.max(mask),
+            BlendMode::Replace => mask,
+        }
+    }
+}
+
+/// Smoothly steps from 0 to 1 as `x` moves from `edge0` to `edge1`.
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    if edge1 <= edge0 {
+        return if x < edge0 { 0.0 } else { 1.0 };
+    }
+
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// The shared parameters of the falloff masks.
+struct Falloff {
+    width: usize,
+    height: usize,
+    center: (f64, f64),
+    radius: f64,
+    falloff: f64,
+}
+
+impl Falloff {
+    /// Gets the offset of a cell from the center of the falloff,
+    /// where an offset of 1 reaches the edge of the map.
+    fn offset(&self, x: usize, y: usize) -> (f64, f64) {
+        let scale_x = (x as f64 + 0.5) / self.width as f64;
+        let scale_y = (y as f64 + 0.5) / self.height as f64;
+
+        (
+            2.0 * (scale_x - self.center.0),
+            2.0 * (scale_y - self.center.1),
+        )
+    }
+
+    /// Gets the value of the falloff at a distance from its center.
+    fn at(&self, distance: f64) -> f64 {
+        1.0 - smoothstep(self.radius * (1.0 - self.falloff), self.radius, distance)
+    }
+}
+
+/// A mask that is 1 inside of a circle and falls off to 0
+/// towards its edge.
+pub struct RadialFalloff(Falloff);
+
+impl RadialFalloff {
+    /// Creates a [`RadialFalloff`].
+    ///
+    /// Parameters:
+    /// - center - the center of the circle as a fraction of the
+    ///   width and height of the map.
+    /// - radius - the radius of the circle, where a radius of 1
+    ///   reaches from the center of the map to its edge.
+    /// - falloff - the fraction of the radius over which the mask
+    ///   falls from 1 to 0.
+    pub fn new(width: usize, height: usize, center: (f64, f64), radius: f64, falloff: f64) -> Self {
+        Self(Falloff {
+            width,
+            height,
+            center,
+            radius,
+            falloff,
+        })
+    }
+}
+
+impl SimpleNoiseGenerator for RadialFalloff {
+    /// Gets the value of the mask at the coordinates `x` and `y`.
+    fn generate(&self, x: usize, y: usize) -> f64 {
+        let (offset_x, offset_y) = self.0.offset(x, y);
+
+        self.0
+            .at((offset_x * offset_x + offset_y * offset_y).sqrt())
+    }
+}
+
+/// A mask that is 1 inside of a square and falls off to 0
+/// towards its edge.
+pub struct SquareFalloff(Falloff);
+
+impl SquareFalloff {
+    /// Creates a [`SquareFalloff`]. The parameters are the same as
+    /// those of a [`RadialFalloff`], with the radius being half of
+    /// the side length of the square.
+    pub fn new(width: usize, height: usize, center: (f64, f64), radius: f64, falloff: f64) -> Self {
+        Self(Falloff {
+            width,
+            height,
+            center,
+            radius,
+            falloff,
+        })
+    }
+}
+
+impl SimpleNoiseGenerator for SquareFalloff {
+    /// Gets the value of the mask at the coordinates `x` and `y`.
+    fn generate(&self, x: usize, y: usize) -> f64 {
+        let (offset_x, offset_y) = self.0.offset(x, y);
+
+        self.0.at(offset_x.abs().max(offset_y.abs()))
+    }
+}
+
+/// A mask loaded from a grayscale image, such as a sketch of the
+/// continents painted by a designer. The image is stretched over the
+/// dimensions of the map and black maps to 0 while white maps to 1.
+pub struct ImageMask {
+    width: usize,
+    height: usize,
+    image: GrayImage,
+}
+
+impl ImageMask {
+    /// Creates an [`ImageMask`] stretched over a map of `width` by
+    /// `height` cells. Images larger than the map are shrunk to it,
+    /// while smaller images are kept as they are and interpolated, so
+    /// that huge maps, such as a world of tiles, don't need an image
+    /// as large as themselves.
+    pub fn from_image(image: DynamicImage, width: usize, height: usize) -> Self {
+        let image_width = image.width().min(width as u32).max(1);
+        let image_height = image.height().min(height as u32).max(1);
+        let image = if (image_width, image_height) == (image.width(), image.height()) {
+            image
+        } else {
+            image.resize_exact(image_width, image_height, FilterType::Triangle)
+        };
+
+        Self {
+            width,
+            height,
+            image: image.into_luma8(),
+        }
+    }
+
+    /// Loads an [`ImageMask`] from the image at `path`.
+    pub fn from_file(path: impl AsRef<str>, width: usize, height: usize) -> ImageResult<Self> {
+        Ok(Self::from_image(image::open(path.as_ref())?, width, height))
+    }
+
+    /// Gets the value of the pixel at `x` and `y`, clamped to the
+    /// edges of the image.
+    fn pixel(&self, x: isize, y: isize) -> f64 {
+        let x = x.clamp(0, self.image.width() as isize - 1) as u32;
+        let y = y.clamp(0, self.image.height() as isize - 1) as u32;
+
+        self.image.get_pixel(x, y).0[0] as f64 / 255.0
+    }
+}
+
+impl SimpleNoiseGenerator for ImageMask {
+    /// Gets the value of the mask at the coordinates `x` and `y`,
+    /// interpolated between the pixels around the center of the cell.
+    fn generate(&self, x: usize, y: usize) -> f64 {
+        let u = (x as f64 + 0.5) / self.width as f64 * self.image.width() as f64 - 0.5;
+        let v = (y as f64 + 0.5) / self.height as f64 * self.image.height() as f64 - 0.5;
+        let (left, top) = (u.floor(), v.floor());
+        let (tx, ty) = (u - left, v - top);
+        let (left, top) = (left as isize, top as isize);
+        let upper = self.pixel(left, top) * (1.0 - tx) + self.pixel(left + 1, top) * tx;
+        let lower = self.pixel(left, top + 1) * (1.0 - tx) + self.pixel(left + 1, top + 1) * tx;
+
+        upper * (1.0 - ty) + lower * ty
+    }
+}
+
+/// A mask along with how it should be combined with the values
+/// it is applied to.
+pub struct MaskLayer {
+    mask: Box<dyn SimpleNoiseGenerator + Send + Sync>,
+    blend: BlendMode,
+    strength: f64,
+}
+
+impl MaskLayer {
+    /// Creates a [`MaskLayer`]. `strength` determines how much of
+    /// the blended value replaces the original value, from 0-1.
+    pub fn new(
+        mask: Box<dyn SimpleNoiseGenerator + Send + Sync>,
+        blend: BlendMode,
+        strength: f64,
+    ) -> Self {
+        Self {
+            mask,
+            blend,
+            strength,
+        }
+    }
+
+    /// Applies the mask to the value at the coordinates `x` and `y`.
+    /// The result is clamped from 0-1.
+    pub fn apply(&self, value: f64, x: usize, y: usize) -> f64 {
+        let blended = self.blend.blend(value, self.mask.generate(x, y));
+
+        (value + (blended - value) * self.strength).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_radial_falloff() {
+        let mask = RadialFalloff::new(100, 100, (0.5, 0.5), 1.0, 0.5);
+
+        assert_eq!(mask.generate(50, 50), 1.0);
+        assert_eq!(mask.generate(0, 0), 0.0);
+        assert!((0.0..1.0).contains(&mask.generate(50, 10)));
+    }
+
+    #[test]
+    fn test_square_falloff_reaches_corners() {
+        let mask = SquareFalloff::new(100, 100, (0.5, 0.5), 1.0, 0.0);
+
+        assert_eq!(mask.generate(1, 1), 1.0);
+    }
+
+    #[test]
+    fn test_image_mask_is_interpolated_without_resizing() {
+        let image = GrayImage::from_raw(2, 1, vec![0, 255]).unwrap();
+        let mask = ImageMask::from_image(DynamicImage::ImageLuma8(image), 131072, 65536);
+        let expected = [(0, 0.0), (49152, 0.25), (81920, 0.75), (131071, 1.0)];
+
+        assert_eq!(mask.image.dimensions(), (2, 1));
+
+        for (x, value) in expected {
+            assert!((mask.generate(x, 100) - value).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_mask_layer_strength() {
+        let layer = MaskLayer::new(
+            Box::new(SquareFalloff::new(10, 10, (0.5, 0.5), 0.1, 0.0)),
+            BlendMode::Multiply,
+            0.5,
+        );
+
+        assert_eq!(layer.apply(0.8, 0, 0), 0.4);
+    }
+}
@@ -0,0 +1,707 @@
+//! This module provides a [`MeshGenerator`] for turning elevation
+//! into a 3D mesh of triangles that can be previewed, rendered or
+//! 3D printed.
+//!
+//! The mesh is a grid with a vertex for every cell that is sampled,
+//! with x running east, y running up and z running south. Meshes can
+//! be saved as OBJ, binary STL or binary glTF files. STL files are
+//! turned so that z runs up, as most slicers expect.
+//!
+//! Flat meshes can be given a base so that they are closed and can
+//! be printed. Meshes can instead be wrapped onto a sphere, with the
+//! east and west edges of the map joined together and the north and
+//! south edges gathered into the poles.
+//!
+//! # Examples
+//!
+//! ```
+//! use ficture::map::{Map, MapMonad};
+//! use ficture::mesh::MeshGeneratorBuilder;
+//!
+//! let elevation = Map::return_single(0.0, 10, 10);
+//! let elevation = elevation.and_then_with_coordinates(|_, x, _| x as f64 / 10.0);
+//!
+//! let generator = MeshGeneratorBuilder::new()
+//!     .vertical_scale(10.0)
+//!     .decimation(2)
+//!     .base(1.0)
+//!     .build();
+//! let mesh = generator.generate(&elevation, None);
+//!
+//! let mut obj = vec![];
+//! mesh.write_obj(&mut obj).unwrap();
+//! ```
+use std::{
+    f64::consts::{FRAC_PI_2, PI, TAU},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use image::Rgba;
+use thiserror::Error;
+
+use crate::map::Map;
+
+/// The error type for mesh errors.
+#[derive(Error, Debug)]
+pub enum MeshError {
+    #[error("unknown mesh format (expected obj, stl or glb, but found {0})")]
+    UnknownFormat(String),
+    #[error("failed to write mesh: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// A result type for [`MeshError`].
+pub type MeshResult<T> = Result<T, MeshError>;
+
+/// The file formats that meshes can be saved as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshFormat {
+    /// A Wavefront OBJ file, with vertex colors after each vertex.
+    Obj,
+    /// A binary STL file.
+    Stl,
+    /// A binary glTF file.
+    Glb,
+}
+
+impl MeshFormat {
+    /// Gets the format of a mesh from the extension of its path.
+    pub fn from_path(path: impl AsRef<Path>) -> MeshResult<Self> {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        match extension.as_str() {
+            "obj" => Ok(Self::Obj),
+            "stl" => Ok(Self::Stl),
+            "glb" => Ok(Self::Glb),
+            _ => Err(MeshError::UnknownFormat(extension)),
+        }
+    }
+}
+
+/// Subtracts the vector `b` from `a`.
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+/// Gets the cross product of the vectors `a` and `b`.
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Scales a vector to a length of 1, or points it up if it has no
+/// length.
+fn normalize_vector(a: [f64; 3]) -> [f64; 3] {
+    let length = (a[0] * a[0] + a[1] * a[1] + a[2] * a[2]).sqrt();
+
+    if length > 0.0 {
+        [a[0] / length, a[1] / length, a[2] / length]
+    } else {
+        [0.0, 1.0, 0.0]
+    }
+}
+
+/// A mesh of triangles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mesh {
+    /// The position of every vertex.
+    pub positions: Vec<[f64; 3]>,
+    /// The color of every vertex, if the mesh is colored.
+    pub colors: Option<Vec<Rgba<u8>>>,
+    /// The indices of the vertices of every triangle, which wind
+    /// counterclockwise when seen from the front.
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl Mesh {
+    /// Gets the positions of the vertices of a triangle.
+    fn corners(&self, triangle: &[usize; 3]) -> [[f64; 3]; 3] {
+        triangle.map(|index| self.positions[index])
+    }
+
+    /// Gets the normal of a triangle, with a length of twice the
+    /// area of the triangle.
+    fn face_normal(&self, triangle: &[usize; 3]) -> [f64; 3] {
+        let [a, b, c] = self.corners(triangle);
+
+        cross(sub(b, a), sub(c, a))
+    }
+
+    /// Gets the normal of every vertex, averaged from the triangles
+    /// around it and weighted by their area.
+    pub fn normals(&self) -> Vec<[f64; 3]> {
+        let mut normals = vec![[0.0; 3]; self.positions.len()];
+
+        for triangle in &self.triangles {
+            let normal = self.face_normal(triangle);
+
+            for index in triangle {
+                for axis in 0..3 {
+                    normals[*index][axis] += normal[axis];
+                }
+            }
+        }
+
+        normals.into_iter().map(normalize_vector).collect()
+    }
+
+    /// Saves the mesh, with the format determined by the extension of
+    /// the path.
+    pub fn save(&self, path: impl AsRef<Path>) -> MeshResult<()> {
+        let format = MeshFormat::from_path(&path)?;
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        match format {
+            MeshFormat::Obj => self.write_obj(&mut writer)?,
+            MeshFormat::Stl => self.write_stl(&mut writer)?,
+            MeshFormat::Glb => self.write_glb(&mut writer)?,
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Writes the mesh as a Wavefront OBJ file. Vertex colors are
+    /// written after the position of each vertex, which most tools
+    /// that read OBJ files understand.
+    pub fn write_obj(&self, mut writer: impl Write) -> std::io::Result<()> {
+        for (index, [x, y, z]) in self.positions.iter().enumerate() {
+            match &self.colors {
+                Some(colors) => {
+                    let [r, g, b, _] = colors[index].0.map(|channel| channel as f64 / 255.0);
+
+                    writeln!(writer, "v {x} {y} {z} {r:.4} {g:.4} {b:.4}")?;
+                }
+                None => writeln!(writer, "v {x} {y} {z}")?,
+            }
+        }
+
+        for [a, b, c] in &self.triangles {
+            writeln!(writer, "f {} {} {}", a + 1, b + 1, c + 1)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the mesh as a binary STL file, turned so that z runs
+    /// up. STL files can't hold colors, so they are left out.
+    pub fn write_stl(&self, mut writer: impl Write) -> std::io::Result<()> {
+        // turning y up into z up keeps the winding of the triangles
+        let turn = |[x, y, z]: [f64; 3]| [x, -z, y];
+        let mut header = [0u8; 80];
+
+        header[..7].copy_from_slice(b"ficture");
+        writer.write_all(&header)?;
+        writer.write_all(&(self.triangles.len() as u32).to_le_bytes())?;
+
+        for triangle in &self.triangles {
+            let normal = turn(normalize_vector(self.face_normal(triangle)));
+
+            for vector in std::iter::once(normal).chain(self.corners(triangle).map(turn)) {
+                for component in vector {
+                    writer.write_all(&(component as f32).to_le_bytes())?;
+                }
+            }
+
+            writer.write_all(&0u16.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the mesh as a binary glTF file with normals and, if the
+    /// mesh is colored, vertex colors.
+    pub fn write_glb(&self, mut writer: impl Write) -> std::io::Result<()> {
+        let count = self.positions.len();
+        let mut buffer: Vec<u8> = vec![];
+        let (mut min, mut max) = ([f32::MAX; 3], [f32::MIN; 3]);
+
+        for position in &self.positions {
+            for axis in 0..3 {
+                let component = position[axis] as f32;
+
+                // the bounds must match the stored values exactly
+                min[axis] = min[axis].min(component);
+                max[axis] = max[axis].max(component);
+                buffer.extend(component.to_le_bytes());
+            }
+        }
+
+        for normal in self.normals() {
+            for component in normal {
+                buffer.extend((component as f32).to_le_bytes());
+            }
+        }
+
+        let colors_offset = buffer.len();
+
+        if let Some(colors) = &self.colors {
+            for color in colors {
+                buffer.extend(color.0);
+            }
+        }
+
+        let indices_offset = buffer.len();
+
+        for index in self.triangles.iter().flatten() {
+            buffer.extend((*index as u32).to_le_bytes());
+        }
+
+        let (color_attribute, color_view, color_accessor) = match self.colors {
+            Some(_) => (
+                r#","COLOR_0":3"#.to_string(),
+                format!(
+                    r#",{{"buffer":0,"byteOffset":{colors_offset},"byteLength":{},"target":34962}}"#,
+                    count * 4
+                ),
+                format!(
+                    r#",{{"bufferView":3,"componentType":5121,"normalized":true,"count":{count},"type":"VEC4"}}"#
+                ),
+            ),
+            None => (String::new(), String::new(), String::new()),
+        };
+        let vector = |values: [f32; 3]| format!("[{},{},{}]", values[0], values[1], values[2]);
+        let json = format!(
+            concat!(
+                r#"{{"asset":{{"version":"2.0","generator":"ficture"}},"scene":0,"#,
+                r#""scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],"#,
+                r#""meshes":[{{"primitives":[{{"attributes":{{"POSITION":0,"NORMAL":1{color_attribute}}},"indices":2}}]}}],"#,
+                r#""buffers":[{{"byteLength":{buffer_length}}}],"#,
+                r#""bufferViews":["#,
+                r#"{{"buffer":0,"byteOffset":0,"byteLength":{vector_length},"target":34962}},"#,
+                r#"{{"buffer":0,"byteOffset":{vector_length},"byteLength":{vector_length},"target":34962}},"#,
+                r#"{{"buffer":0,"byteOffset":{indices_offset},"byteLength":{indices_length},"target":34963}}"#,
+                r#"{color_view}],"#,
+                r#""accessors":["#,
+                r#"{{"bufferView":0,"componentType":5126,"count":{count},"type":"VEC3","min":{min},"max":{max}}},"#,
+                r#"{{"bufferView":1,"componentType":5126,"count":{count},"type":"VEC3"}},"#,
+                r#"{{"bufferView":2,"componentType":5125,"count":{index_count},"type":"SCALAR"}}"#,
+                r#"{color_accessor}]}}"#,
+            ),
+            color_attribute = color_attribute,
+            buffer_length = buffer.len(),
+            vector_length = count * 12,
+            indices_offset = indices_offset,
+            indices_length = buffer.len() - indices_offset,
+            color_view = color_view,
+            count = count,
+            min = vector(min),
+            max = vector(max),
+            index_count = self.triangles.len() * 3,
+            color_accessor = color_accessor,
+        );
+
+        // both chunks must be padded to a multiple of 4 bytes, with
+        // spaces for the JSON and zeros for the binary data
+        let mut json = json.into_bytes();
+
+        while !json.len().is_multiple_of(4) {
+            json.push(b' ');
+        }
+        while !buffer.len().is_multiple_of(4) {
+            buffer.push(0);
+        }
+
+        let length = 12 + 8 + json.len() + 8 + buffer.len();
+
+        writer.write_all(b"glTF")?;
+        writer.write_all(&2u32.to_le_bytes())?;
+        writer.write_all(&(length as u32).to_le_bytes())?;
+        writer.write_all(&(json.len() as u32).to_le_bytes())?;
+        writer.write_all(b"JSON")?;
+        writer.write_all(&json)?;
+        writer.write_all(&(buffer.len() as u32).to_le_bytes())?;
+        writer.write_all(b"BIN\0")?;
+        writer.write_all(&buffer)?;
+
+        Ok(())
+    }
+}
+
+/// A structure for generating meshes from elevation.
+pub struct MeshGenerator {
+    vertical_scale: f64,
+    decimation: usize,
+    base: f64,
+    sphere: bool,
+}
+
+impl MeshGenerator {
+    /// Gets the coordinates along an axis of `length` cells that are
+    /// sampled. When the axis doesn't wrap, the last cell is always
+    /// sampled so that the mesh covers the whole map.
+    fn samples(&self, length: usize, wraps: bool) -> Vec<usize> {
+        if length == 0 {
+            return vec![];
+        }
+
+        let mut samples: Vec<usize> = (0..length).step_by(self.decimation).collect();
+
+        if !wraps && samples.last() != Some(&(length - 1)) {
+            samples.push(length - 1);
+        }
+
+        samples
+    }
+
+    /// Gets the position of the vertex for the cell at `x` and `y`,
+    /// raised to `value` of the highest possible elevation.
+    fn position(&self, elevation: &Map<f64>, x: usize, y: usize, value: f64) -> [f64; 3] {
+        let height = value * self.vertical_scale;
+
+        if !self.sphere {
+            return [x as f64, height, y as f64];
+        }
+
+        // the radius is chosen so that cells on the equator are as
+        // wide as they are on a flat mesh
+        let radius = elevation.width() as f64 / TAU + height;
+        let longitude = x as f64 / elevation.width() as f64 * TAU;
+        let latitude = FRAC_PI_2 - y as f64 / (elevation.height() - 1).max(1) as f64 * PI;
+
+        [
+            radius * latitude.cos() * longitude.cos(),
+            radius * latitude.sin(),
+            -radius * latitude.cos() * longitude.sin(),
+        ]
+    }
+
+    /// Generates a mesh from a map of elevation, with colors for the
+    /// vertices taken from a map of colors if one is given.
+    pub fn generate(&self, elevation: &Map<f64>, colors: Option<&Map<Rgba<u8>>>) -> Mesh {
+        let columns = self.samples(elevation.width(), self.sphere);
+        let rows = self.samples(elevation.height(), false);
+
+        if columns.is_empty() || rows.is_empty() {
+            return Mesh {
+                positions: vec![],
+                colors: colors.map(|_| vec![]),
+                triangles: vec![],
+            };
+        }
+
+        // a sphere gathers its first and last rows into a single
+        // vertex at each pole
+        let is_pole = |row: usize| self.sphere && (row == 0 || row == rows.len() - 1);
+        let index = |column: usize, row: usize| {
+            if !self.sphere {
+                row * columns.len() + column
+            } else if row == 0 {
+                0
+            } else if is_pole(row) {
+                1 + (rows.len() - 2) * columns.len()
+            } else {
+                1 + (row - 1) * columns.len() + column
+            }
+        };
+        let mut positions = vec![];
+        let mut vertex_colors = vec![];
+        let mut triangles = vec![];
+
+        for (row, y) in rows.iter().enumerate() {
+            if is_pole(row) {
+                // the pole sits at the average elevation of its row
+                // and takes the average color
+                let count = columns.len() as f64;
+                let value = columns.iter().map(|x| elevation.get(*x, *y)).sum::<f64>() / count;
+
+                positions.push(self.position(elevation, 0, *y, value));
+
+                if let Some(colors) = colors {
+                    let channel = |i: usize| {
+                        let sum: f64 = columns.iter().map(|x| colors.get(*x, *y).0[i] as f64).sum();
+
+                        (sum / count).round() as u8
+                    };
+
+                    vertex_colors.push(Rgba([channel(0), channel(1), channel(2), channel(3)]));
+                }
+                continue;
+            }
+
+            for x in &columns {
+                positions.push(self.position(elevation, *x, *y, *elevation.get(*x, *y)));
+
+                if let Some(colors) = colors {
+                    vertex_colors.push(*colors.get(*x, *y));
+                }
+            }
+        }
+
+        // a sphere joins its last column back to its first
+        let quad_columns = if self.sphere {
+            columns.len()
+        } else {
+            columns.len() - 1
+        };
+
+        for row in 0..rows.len() - 1 {
+            for column in 0..quad_columns {
+                let next_column = (column + 1) % columns.len();
+                let (a, b) = (index(column, row), index(next_column, row));
+                let (c, d) = (index(column, row + 1), index(next_column, row + 1));
+
+                // next to the poles, one triangle of each quad
+                // collapses, which leaves a fan around the pole
+                for triangle in [[a, c, b], [b, c, d]] {
+                    if triangle[0] != triangle[1]
+                        && triangle[1] != triangle[2]
+                        && triangle[0] != triangle[2]
+                    {
+                        triangles.push(triangle);
+                    }
+                }
+            }
+        }
+
+        let mut mesh = Mesh {
+            positions,
+            colors: colors.map(|_| vertex_colors),
+            triangles,
+        };
+
+        if !self.sphere && self.base > 0.0 {
+            self.add_base(&mut mesh, columns.len(), rows.len());
+        }
+
+        mesh
+    }
+
+    /// Closes a flat mesh with walls around its edges down to a flat
+    /// bottom below the lowest possible elevation.
+    fn add_base(&self, mesh: &mut Mesh, columns: usize, rows: usize) {
+        let index = |column: usize, row: usize| row * columns + column;
+        let bottom = -self.base;
+
+        // the top vertices around the edge of the grid, in order
+        let perimeter: Vec<usize> = (0..columns)
+            .map(|column| index(column, 0))
+            .chain((1..rows).map(|row| index(columns - 1, row)))
+            .chain((0..columns - 1).rev().map(|column| index(column, rows - 1)))
+            .chain((1..rows - 1).rev().map(|row| index(0, row)))
+            .collect();
+        let first_bottom = mesh.positions.len();
+
+        for top in &perimeter {
+            let [x, _, z] = mesh.positions[*top];
+
+            mesh.positions.push([x, bottom, z]);
+
+            if let Some(colors) = &mut mesh.colors {
+                colors.push(colors[*top]);
+            }
+        }
+
+        let [max_x, _, max_z] = mesh.positions[index(columns - 1, rows - 1)];
+        let center = mesh.positions.len();
+
+        mesh.positions.push([max_x / 2.0, bottom, max_z / 2.0]);
+
+        if let Some(colors) = &mut mesh.colors {
+            colors.push(colors[0]);
+        }
+
+        for (i, top) in perimeter.iter().enumerate() {
+            let next = (i + 1) % perimeter.len();
+            let next_top = perimeter[next];
+            let (bottom, next_bottom) = (first_bottom + i, first_bottom + next);
+
+            mesh.triangles.push([*top, next_top, bottom]);
+            mesh.triangles.push([next_top, next_bottom, bottom]);
+            mesh.triangles.push([center, bottom, next_bottom]);
+        }
+    }
+}
+
+/// A builder for the [`MeshGenerator`].
+pub struct MeshGeneratorBuilder {
+    vertical_scale: f64,
+    decimation: usize,
+    base: f64,
+    sphere: bool,
+}
+
+impl MeshGeneratorBuilder {
+    /// Creates the [`MeshGeneratorBuilder`].
+    pub fn new() -> Self {
+        Self {
+            vertical_scale: 100.0,
+            decimation: 1,
+            base: 0.0,
+            sphere: false,
+        }
+    }
+
+    /// Sets how tall the highest possible elevation is, where each
+    /// cell is 1 wide.
+    pub fn vertical_scale(mut self, vertical_scale: f64) -> Self {
+        self.vertical_scale = vertical_scale;
+        self
+    }
+
+    /// Sets how many cells apart the vertices of the mesh are.
+    pub fn decimation(mut self, decimation: usize) -> Self {
+        self.decimation = decimation;
+        self
+    }
+
+    /// Sets how far below the lowest possible elevation the bottom of
+    /// the base is. Flat meshes with a base are closed so that they
+    /// can be printed. Spheres have no base.
+    pub fn base(mut self, base: f64) -> Self {
+        self.base = base;
+        self
+    }
+
+    /// Sets whether the mesh is wrapped onto a sphere.
+    pub fn sphere(mut self, sphere: bool) -> Self {
+        self.sphere = sphere;
+        self
+    }
+
+    /// Construct the [`MeshGenerator`] based on the
+    /// defined attributes.
+    pub fn build(self) -> MeshGenerator {
+        MeshGenerator {
+            vertical_scale: self.vertical_scale,
+            decimation: self.decimation.max(1),
+            base: self.base,
+            sphere: self.sphere,
+        }
+    }
+}
+
+impl Default for MeshGeneratorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::map::MapMonad;
+
+    /// Gets the volume enclosed by a mesh, which is only positive if
+    /// the mesh is closed and its triangles face outwards.
+    fn volume(mesh: &Mesh) -> f64 {
+        mesh.triangles
+            .iter()
+            .map(|triangle| {
+                let [a, b, c] = mesh.corners(triangle);
+                let [x, y, z] = cross(b, c);
+
+                (a[0] * x + a[1] * y + a[2] * z) / 6.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_base_closes_mesh() {
+        let elevation = Map::return_single(0.5, 9, 5);
+        let mesh = MeshGeneratorBuilder::new()
+            .vertical_scale(2.0)
+            .decimation(3)
+            .base(1.0)
+            .build()
+            .generate(&elevation, None);
+
+        // sampled at x = 0, 3, 6, 8 and y = 0, 3, 4, making a box
+        // 8 wide, 4 deep and 2 tall
+        assert_eq!(mesh.positions.len(), 12 + 10 + 1);
+        assert!((volume(&mesh) - 64.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_empty_map_has_empty_mesh() {
+        for sphere in [false, true] {
+            let mesh = MeshGeneratorBuilder::new()
+                .base(1.0)
+                .sphere(sphere)
+                .build()
+                .generate(&Map::from_vec(vec![], 0, 4), None);
+
+            assert!(mesh.positions.is_empty() && mesh.triangles.is_empty());
+            assert!(mesh.write_obj(vec![]).is_ok());
+            assert!(mesh.write_stl(vec![]).is_ok());
+            assert!(mesh.write_glb(vec![]).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_sphere_faces_outwards() {
+        let elevation = Map::return_single(0.0, 16, 9)
+            .and_then_with_coordinates(|_, x, y| ((x + y) % 3) as f64 / 3.0);
+        let mesh = MeshGeneratorBuilder::new()
+            .vertical_scale(0.5)
+            .sphere(true)
+            .build()
+            .generate(&elevation, None);
+        let radius = 16.0 / TAU;
+
+        assert_eq!(mesh.positions.len(), 16 * 7 + 2);
+        assert!(volume(&mesh) > 4.0 / 3.0 * PI * radius.powi(3) * 0.8);
+    }
+
+    #[test]
+    fn test_sphere_poles_are_fans() {
+        let elevation = Map::return_single(0.0, 8, 5);
+        let mesh = MeshGeneratorBuilder::new()
+            .sphere(true)
+            .build()
+            .generate(&elevation, None);
+        let area = |triangle: &[usize; 3]| {
+            let [a, b, c] = mesh.corners(triangle);
+            let [x, y, z] = cross(sub(b, a), sub(c, a));
+
+            (x * x + y * y + z * z).sqrt()
+        };
+        let south = mesh.positions.len() - 1;
+
+        // a fan of 8 triangles around each pole and 2 triangles for
+        // each of the 8 by 2 quads between the inner rows
+        assert_eq!(mesh.positions.len(), 8 * 3 + 2);
+        assert_eq!(mesh.triangles.len(), 8 * 2 + 8 * 2 * 2);
+        assert_eq!(
+            mesh.triangles
+                .iter()
+                .filter(|triangle| triangle.contains(&0) || triangle.contains(&south))
+                .count(),
+            16
+        );
+        assert!(mesh.triangles.iter().all(|triangle| area(triangle) > 0.0));
+    }
+
+    #[test]
+    fn test_glb_is_padded() {
+        let elevation = Map::return_single(0.25, 3, 3);
+        let colors = Map::return_single(Rgba([10, 20, 30, 255]), 3, 3);
+        let mesh = MeshGeneratorBuilder::new()
+            .build()
+            .generate(&elevation, Some(&colors));
+        let mut glb = vec![];
+
+        mesh.write_glb(&mut glb).unwrap();
+
+        let json_length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+
+        assert_eq!(&glb[..4], b"glTF");
+        assert_eq!(
+            u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize,
+            glb.len()
+        );
+        assert_eq!(glb.len() % 4, 0);
+        assert_eq!(&glb[20 + json_length + 4..20 + json_length + 8], b"BIN\0");
+    }
+}
@@ -0,0 +1,244 @@
+//! This module provides [`MapMetadata`] for embedding how a map was
+//! generated inside the PNG that it is saved as, so that a shared
+//! map can be generated again.
+//!
+//! The metadata is written as PNG text chunks, which are kept by
+//! most tools that copy or move images. The config and the command
+//! line arguments are written as YAML in international text chunks
+//! since they may hold any character, while everything else is
+//! written as plain text chunks. The arguments are kept as a list so
+//! that arguments holding spaces aren't split apart.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use image::{DynamicImage, RgbImage};
+//!
+//! use ficture::metadata::MapMetadata;
+//!
+//! let metadata = MapMetadata {
+//!     version: String::from(env!("CARGO_PKG_VERSION")),
+//!     seed: Some(42),
+//!     width: 10,
+//!     height: 10,
+//!     sea_level: 0.05,
+//!     config: String::from("noise_generators: {}\nbiome_maps: {}\n"),
+//!     config_hash: None,
+//!     arguments: vec![String::from("--width"), String::from("10")],
+//! };
+//! let image = DynamicImage::ImageRgb8(RgbImage::new(10, 10));
+//!
+//! metadata.save_png(&image, "image.png").unwrap();
+//!
+//! assert_eq!(MapMetadata::read_png("image.png").unwrap(), metadata);
+//! ```
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+    str::FromStr,
+};
+
+use image::DynamicImage;
+use png::{BitDepth, ColorType, Decoder, Encoder};
+use thiserror::Error;
+
+const SOFTWARE: &str = "Software";
+const SEED: &str = "ficture-seed";
+const WIDTH: &str = "ficture-width";
+const HEIGHT: &str = "ficture-height";
+const SEA_LEVEL: &str = "ficture-sea-level";
+const CONFIG: &str = "ficture-config";
+const CONFIG_HASH: &str = "ficture-config-hash";
+const ARGUMENTS: &str = "ficture-arguments";
+
+/// The error type for metadata errors.
+#[derive(Error, Debug)]
+pub enum MetadataError {
+    #[error("failed to access image: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to write PNG: {0}")]
+    Encoding(#[from] png::EncodingError),
+    #[error("failed to read PNG: {0}")]
+    Decoding(#[from] png::DecodingError),
+    #[error("missing metadata (expected a {0} text chunk, but found none)")]
+    MissingField(&'static str),
+    #[error("invalid metadata (expected a valid {0} text chunk, but found {1})")]
+    InvalidField(&'static str, String),
+    #[error("failed to serialize metadata: {0}")]
+    Serialization(#[from] serde_yaml::Error),
+}
+
+/// A result type for [`MetadataError`].
+pub type MetadataResult<T> = Result<T, MetadataError>;
+
+/// Parses the value of a text chunk.
+fn parse_field<T: FromStr>(keyword: &'static str, value: String) -> MetadataResult<T> {
+    value
+        .parse()
+        .map_err(|_| MetadataError::InvalidField(keyword, value))
+}
+
+/// A description of how a map was generated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapMetadata {
+    /// The version of ficture that generated the map.
+    pub version: String,
+    /// The seed that replaced the seeds of the config, if any. The
+    /// config already holds the seed, so this is only a reminder.
+    pub seed: Option<u32>,
+    /// The width of the map in cells.
+    pub width: usize,
+    /// The height of the map in cells.
+    pub height: usize,
+    /// The elevation of the sea level.
+    pub sea_level: f64,
+    /// The full config used to generate the map, as YAML.
+    pub config: String,
+    /// The hash of the config file that the map was generated from,
+    /// if it was generated from a file.
+    pub config_hash: Option<u64>,
+    /// The command line arguments used to generate the map.
+    pub arguments: Vec<String>,
+}
+
+impl MapMetadata {
+    /// Saves an image as a PNG with the metadata in its text
+    /// chunks. Images that aren't 8 bit RGB or RGBA are converted to
+    /// 8 bit RGBA.
+    pub fn save_png(&self, image: &DynamicImage, path: impl AsRef<Path>) -> MetadataResult<()> {
+        let (color_type, data) = match image {
+            DynamicImage::ImageRgb8(image) => (ColorType::Rgb, image.as_raw().clone()),
+            image => (ColorType::Rgba, image.to_rgba8().into_raw()),
+        };
+        let writer = BufWriter::new(File::create(path)?);
+        let mut encoder = Encoder::new(writer, image.width(), image.height());
+
+        encoder.set_color(color_type);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.add_text_chunk(
+            SOFTWARE.to_string(),
+            format!("ficture-generator {}", self.version),
+        )?;
+
+        if let Some(seed) = self.seed {
+            encoder.add_text_chunk(SEED.to_string(), seed.to_string())?;
+        }
+
+        encoder.add_text_chunk(WIDTH.to_string(), self.width.to_string())?;
+        encoder.add_text_chunk(HEIGHT.to_string(), self.height.to_string())?;
+        encoder.add_text_chunk(SEA_LEVEL.to_string(), self.sea_level.to_string())?;
+        encoder.add_itxt_chunk(
+            ARGUMENTS.to_string(),
+            serde_yaml::to_string(&self.arguments)?,
+        )?;
+        encoder.add_itxt_chunk(CONFIG.to_string(), self.config.clone())?;
+
+        if let Some(config_hash) = self.config_hash {
+            encoder.add_text_chunk(CONFIG_HASH.to_string(), format!("{config_hash:016x}"))?;
+        }
+
+        let mut writer = encoder.write_header()?;
+
+        writer.write_image_data(&data)?;
+        writer.finish()?;
+
+        Ok(())
+    }
+
+    /// Reads the metadata from the text chunks of a PNG.
+    pub fn read_png(path: impl AsRef<Path>) -> MetadataResult<Self> {
+        let decoder = Decoder::new(BufReader::new(File::open(path)?));
+        let reader = decoder.read_info()?;
+        let info = reader.info();
+        let mut text: Vec<(String, String)> = info
+            .uncompressed_latin1_text
+            .iter()
+            .map(|chunk| (chunk.keyword.clone(), chunk.text.clone()))
+            .collect();
+
+        for chunk in &info.utf8_text {
+            text.push((chunk.keyword.clone(), chunk.get_text()?));
+        }
+
+        let field = |keyword: &'static str| {
+            text.iter()
+                .find(|(key, _)| key == keyword)
+                .map(|(_, value)| value.clone())
+                .ok_or(MetadataError::MissingField(keyword))
+        };
+        let software = field(SOFTWARE)?;
+        let version = software
+            .strip_prefix("ficture-generator ")
+            .ok_or_else(|| MetadataError::InvalidField(SOFTWARE, software.clone()))?
+            .to_string();
+        let seed = match field(SEED) {
+            Ok(seed) => Some(parse_field(SEED, seed)?),
+            Err(_) => None,
+        };
+        let config_hash = match field(CONFIG_HASH) {
+            Ok(config_hash) => Some(
+                u64::from_str_radix(&config_hash, 16)
+                    .map_err(|_| MetadataError::InvalidField(CONFIG_HASH, config_hash))?,
+            ),
+            Err(_) => None,
+        };
+        let arguments = match field(ARGUMENTS) {
+            Ok(arguments) => serde_yaml::from_str(&arguments)
+                .map_err(|_| MetadataError::InvalidField(ARGUMENTS, arguments))?,
+            Err(_) => vec![],
+        };
+
+        Ok(Self {
+            version,
+            seed,
+            width: parse_field(WIDTH, field(WIDTH)?)?,
+            height: parse_field(HEIGHT, field(HEIGHT)?)?,
+            sea_level: parse_field(SEA_LEVEL, field(SEA_LEVEL)?)?,
+            config: field(CONFIG)?,
+            config_hash,
+            arguments,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+    use image::RgbaImage;
+
+    #[test]
+    fn test_metadata_round_trips() {
+        let config = Config::from_file("config/config.yaml").unwrap();
+        let metadata = MapMetadata {
+            version: String::from("1.2.3"),
+            seed: None,
+            width: 4,
+            height: 3,
+            sea_level: 0.05,
+            config: config.to_yaml().unwrap(),
+            config_hash: Some(0x0123_4567_89ab_cdef),
+            arguments: ["--width", "4", "--output", "my map.png", "--alpha"]
+                .map(String::from)
+                .to_vec(),
+        };
+        let image = DynamicImage::ImageRgba8(RgbaImage::new(4, 3));
+        let path =
+            std::env::temp_dir().join(format!("ficture_test_metadata_{}.png", std::process::id()));
+
+        metadata.save_png(&image, &path).unwrap();
+
+        let read = MapMetadata::read_png(&path).unwrap();
+        let width = image::open(&path).unwrap().width();
+
+        std::fs::remove_file(&path).unwrap();
+
+        let read_config = Config::from_yaml(&read.config).unwrap();
+
+        assert_eq!(read, metadata);
+        assert!(read_config.validate().is_ok());
+        assert!(read_config.get_color_evaluator("default").is_some());
+        assert_eq!(width, 4);
+    }
+}
@@ -50,7 +50,14 @@ impl SimplexNoiseGenerator {
     /// Creates a [`SimplexNoiseGenerator`]. Pre-calculates the noise frequencies and
     /// amplitudes as well as the coordinates to use for wrapping the map along the
     /// east-west axis.
-    fn new(width: usize, height: usize, octaves: usize, persistence: f64, lacunarity: f64) -> Self {
+    fn new(
+        width: usize,
+        height: usize,
+        octaves: usize,
+        persistence: f64,
+        lacunarity: f64,
+        seed: u32,
+    ) -> Self {
         let mut amplitude = 1.0;
         let mut frequencies = vec![];
         let mut amplitudes = vec![];
@@ -80,7 +87,7 @@ impl SimplexNoiseGenerator {
             frequencies,
             amplitudes,
             circle_coords,
-            noise: Simplex::new(2),
+            noise: Simplex::new(seed),
         }
     }
 }
@@ -116,6 +123,14 @@ pub trait NoiseGeneratorBuilder {
     fn octaves(self, octaves: usize) -> Self;
     fn persistence(self, persistence: f64) -> Self;
     fn lacunarity(self, lacunarity: f64) -> Self;
+    /// Sets the seed of the noise. Builders that can't be seeded
+    /// ignore it.
+    fn seed(self, _seed: u32) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
     fn build(self) -> Box<dyn SimpleNoiseGenerator + Send + Sync>;
 }
 
@@ -126,6 +141,7 @@ pub struct SimplexNoiseGeneratorBuilder {
     octaves: usize,
     persistence: f64,
     lacunarity: f64,
+    seed: u32,
 }
 
 impl NoiseGeneratorBuilder for SimplexNoiseGeneratorBuilder {
@@ -137,6 +153,7 @@ impl NoiseGeneratorBuilder for SimplexNoiseGeneratorBuilder {
             octaves: 6,
             persistence: 2.0,
             lacunarity: 3.0,
+            seed: 2,
         }
     }
 
@@ -164,6 +181,13 @@ impl NoiseGeneratorBuilder for SimplexNoiseGeneratorBuilder {
         self
     }
 
+    /// Sets the seed of the noise. The same seed always generates
+    /// the same noise.
+    fn seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self
+    }
+
     /// Construct the [`SimplexNoiseGenerator`] based on
     /// the defined attributes.
     fn build(self) -> Box<dyn SimpleNoiseGenerator + Send + Sync> {
@@ -173,6 +197,7 @@ impl NoiseGeneratorBuilder for SimplexNoiseGeneratorBuilder {
             self.octaves,
             self.persistence,
             self.lacunarity,
+            self.seed,
         ))
     }
 }
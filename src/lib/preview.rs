@@ -0,0 +1,230 @@
+//! This module provides functions for previewing maps in a terminal,
+//! for when the saved image can't be looked at, such as over SSH.
+//!
+//! Maps are shrunk to fit a number of columns by averaging the
+//! pixels under each character. In color, every character is an
+//! upper half block colored with 24 bit ANSI escapes, with the top
+//! pixel as the foreground and the bottom pixel as the background, so
+//! that each character shows two pixels. Terminals without 24 bit
+//! color can preview the elevation as ASCII characters instead.
+//!
+//! # Examples
+//!
+//! ```
+//! use image::Rgb;
+//!
+//! use ficture::map::{Map, MapMonad};
+//! use ficture::preview::render_truecolor;
+//!
+//! let map = Map::return_single(Rgb([40, 90, 200]), 160, 90);
+//! let preview = render_truecolor(&map, 80);
+//!
+//! assert_eq!(preview.lines().count(), 23);
+//! ```
+use image::Rgb;
+
+use crate::{map::Map, utils::normalize};
+
+/// The characters used for land in ASCII previews, from lowest to
+/// highest.
+const LAND_RAMP: &[u8] = b".:-=+*#%@";
+
+/// The character used for the ocean in ASCII previews.
+const OCEAN: char = '~';
+
+/// Asks the terminal attached to standard output for its width in
+/// columns.
+#[cfg(unix)]
+fn query_terminal_width() -> Option<usize> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ only writes the size of the terminal into
+    // the winsize that it is given, which lives for the whole call
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+
+    (result == 0 && size.ws_col > 0).then_some(size.ws_col as usize)
+}
+
+/// Asks the terminal attached to standard output for its width in
+/// columns, which isn't supported on this platform.
+#[cfg(not(unix))]
+fn query_terminal_width() -> Option<usize> {
+    None
+}
+
+/// Gets the width of the terminal in columns. The terminal attached
+/// to standard output is asked first, falling back to the `COLUMNS`
+/// environment variable, such as when the output is piped, or 80 if
+/// neither is known.
+pub fn terminal_width() -> usize {
+    query_terminal_width()
+        .or_else(|| {
+            std::env::var("COLUMNS")
+                .ok()
+                .and_then(|columns| columns.trim().parse().ok())
+                .filter(|columns| *columns > 0)
+        })
+        .unwrap_or(80)
+}
+
+/// Guesses whether the terminal can show 24 bit colors, which is
+/// advertised with `COLORTERM` set to `truecolor` or `24bit`. The
+/// `NO_COLOR` convention is followed and dumb terminals are treated
+/// as colorless.
+pub fn supports_truecolor() -> bool {
+    let term = std::env::var("TERM").unwrap_or_default();
+    let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+
+    std::env::var_os("NO_COLOR").is_none()
+        && !term.is_empty()
+        && term != "dumb"
+        && matches!(colorterm.as_str(), "truecolor" | "24bit")
+}
+
+/// Shrinks a map to `width` by `height` by averaging the channels of
+/// the cells that fall into each new cell.
+fn downsample<T: Clone + Send, const N: usize>(
+    map: &Map<T>,
+    width: usize,
+    height: usize,
+    channels: impl Fn(&T) -> [f64; N],
+) -> Vec<[f64; N]> {
+    let span = |i: usize, from: usize, to: usize| {
+        let start = i * from / to;
+
+        start..((i + 1) * from / to).max(start + 1)
+    };
+    let mut cells = Vec::with_capacity(width * height);
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0; N];
+            let mut count = 0.0;
+
+            for source_y in span(y, map.height(), height) {
+                for source_x in span(x, map.width(), width) {
+                    for (sum, channel) in sum.iter_mut().zip(channels(map.get(source_x, source_y)))
+                    {
+                        *sum += channel;
+                    }
+                    count += 1.0;
+                }
+            }
+
+            cells.push(sum.map(|sum| sum / count));
+        }
+    }
+
+    cells
+}
+
+/// Gets the width and height of a preview of a map fit to `columns`,
+/// where each character holds `pixels_per_row` pixels vertically and
+/// characters are twice as tall as they are wide.
+fn preview_size<T: Clone + Send>(
+    map: &Map<T>,
+    columns: usize,
+    pixels_per_row: usize,
+) -> (usize, usize) {
+    let width = columns.clamp(1, map.width().max(1));
+    let rows = (map.height() * width)
+        .div_ceil(map.width().max(1) * 2)
+        .max(1);
+
+    (width, rows * pixels_per_row)
+}
+
+/// Renders a colored map as 24 bit ANSI colored half blocks, shrunk to
+/// fit `columns` characters across. Empty maps render as nothing.
+pub fn render_truecolor(map: &Map<Rgb<u8>>, columns: usize) -> String {
+    if map.is_empty() {
+        return String::new();
+    }
+
+    let (width, height) = preview_size(map, columns, 2);
+    let pixels = downsample(map, width, height, |pixel| {
+        pixel.0.map(|channel| channel as f64)
+    });
+    let color = |pixel: [f64; 3]| pixel.map(|channel| channel.round() as u8);
+    let mut preview = String::new();
+
+    for y in (0..height).step_by(2) {
+        for x in 0..width {
+            let [r, g, b] = color(pixels[y * width + x]);
+            let [br, bg, bb] = color(pixels[(y + 1) * width + x]);
+
+            preview.push_str(&format!(
+                "\x1b[38;2;{r};{g};{b}m\x1b[48;2;{br};{bg};{bb}m\u{2580}"
+            ));
+        }
+
+        preview.push_str("\x1b[0m\n");
+    }
+
+    preview
+}
+
+/// Renders an elevation map as ASCII characters, shrunk to fit
+/// `columns` characters across. The ocean is drawn with `~`, while
+/// land is drawn with denser characters the higher it is. Empty maps
+/// render as nothing.
+pub fn render_ascii(elevation: &Map<f64>, columns: usize, sea_level: f64) -> String {
+    if elevation.is_empty() {
+        return String::new();
+    }
+
+    let (width, height) = preview_size(elevation, columns, 1);
+    let cells = downsample(elevation, width, height, |elevation| [*elevation]);
+    let mut preview = String::new();
+
+    for row in cells.chunks(width) {
+        for [elevation] in row {
+            if *elevation < sea_level {
+                preview.push(OCEAN);
+            } else {
+                let height = normalize(*elevation, sea_level, 1.0).clamp(0.0, 1.0);
+                let index = (height * (LAND_RAMP.len() - 1) as f64).round() as usize;
+
+                preview.push(LAND_RAMP[index] as char);
+            }
+        }
+
+        preview.push('\n');
+    }
+
+    preview
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_half_blocks_hold_two_pixels() {
+        let map = Map::from_vec(vec![Rgb([255, 0, 0]), Rgb([0, 0, 255])], 1, 2);
+        let preview = render_truecolor(&map, 80);
+
+        assert_eq!(
+            preview,
+            "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m\u{2580}\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_ascii_preview_is_shrunk() {
+        let elevation = Map::from_vec(vec![0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0], 4, 2);
+        let preview = render_ascii(&elevation, 2, 0.05);
+
+        assert_eq!(preview, "~@\n");
+    }
+
+    #[test]
+    fn test_empty_maps_render_nothing() {
+        assert!(render_truecolor(&Map::from_vec(vec![], 0, 4), 80).is_empty());
+        assert!(render_ascii(&Map::from_vec(vec![], 4, 0), 80, 0.05).is_empty());
+    }
+}
@@ -0,0 +1,314 @@
+//! This module provides a [`RasterExporter`] for saving layers of
+//! values, such as elevation or moisture, as 32 bit floating point
+//! rasters without losing precision to colors.
+//!
+//! Rasters can be saved as TIFF, OpenEXR or PFM files. Each raster
+//! carries metadata with the name of the layer, the range of its
+//! values and any parameters used to generate it. TIFF files store
+//! the metadata in their image description and OpenEXR files store
+//! it as header attributes. PFM files have no room for metadata, so
+//! it is saved next to them in a file with a `.meta` extension.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ficture::map::{Map, MapMonad};
+//! use ficture::raster::RasterExporterBuilder;
+//!
+//! let elevation = Map::return_single(0.0, 10, 10);
+//! let elevation = elevation.and_then_with_coordinates(|_, x, _| x as f64 / 10.0);
+//!
+//! let exporter = RasterExporterBuilder::new()
+//!     .name("elevation")
+//!     .parameter("sea_level", "0.05")
+//!     .build();
+//!
+//! exporter.save(&elevation, "elevation.exr").unwrap();
+//! ```
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use exr::prelude::{
+    AnyChannel, AnyChannels, AttributeValue, Encoding, FlatSamples, Image, Layer, LayerAttributes,
+    Text, WritableImage,
+};
+use thiserror::Error;
+use tiff::{
+    encoder::{colortype::Gray32Float, TiffEncoder},
+    tags::Tag,
+};
+
+use crate::map::Map;
+
+/// The error type for raster errors.
+#[derive(Error, Debug)]
+pub enum RasterError {
+    #[error("unknown raster format (expected tif, tiff, exr or pfm, but found {0})")]
+    UnknownFormat(String),
+    #[error("failed to write raster: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to write TIFF: {0}")]
+    Tiff(#[from] tiff::TiffError),
+    #[error("failed to write OpenEXR: {0}")]
+    Exr(#[from] exr::error::Error),
+}
+
+/// A result type for [`RasterError`].
+pub type RasterResult<T> = Result<T, RasterError>;
+
+/// The file formats that rasters can be saved as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RasterFormat {
+    /// A single channel 32 bit floating point TIFF.
+    Tiff,
+    /// A single channel 32 bit floating point OpenEXR image.
+    Exr,
+    /// A grayscale portable float map.
+    Pfm,
+}
+
+impl RasterFormat {
+    /// Gets the format of a raster from the extension of its path.
+    pub fn from_path(path: impl AsRef<Path>) -> RasterResult<Self> {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        match extension.as_str() {
+            "tif" | "tiff" => Ok(Self::Tiff),
+            "exr" => Ok(Self::Exr),
+            "pfm" => Ok(Self::Pfm),
+            _ => Err(RasterError::UnknownFormat(extension)),
+        }
+    }
+}
+
+/// A structure for saving layers of values as floating point
+/// rasters.
+pub struct RasterExporter {
+    name: String,
+    parameters: Vec<(String, String)>,
+}
+
+impl RasterExporter {
+    /// Gets the metadata of a raster of the given map, as pairs of
+    /// keys and values.
+    pub fn metadata(&self, map: &Map<f64>) -> Vec<(String, String)> {
+        let (min, max) = map.iter().fold((f64::MAX, f64::MIN), |(min, max), value| {
+            (min.min(*value), max.max(*value))
+        });
+        let mut metadata = vec![
+            (String::from("layer"), self.name.clone()),
+            (String::from("min"), min.to_string()),
+            (String::from("max"), max.to_string()),
+        ];
+
+        metadata.extend(self.parameters.iter().cloned());
+        metadata
+    }
+
+    /// Gets the metadata of a raster as lines of `key=value`.
+    fn metadata_text(&self, map: &Map<f64>) -> String {
+        self.metadata(map)
+            .iter()
+            .map(|(key, value)| format!("{key}={value}\n"))
+            .collect()
+    }
+
+    /// Saves a map as a raster, with the format determined by the
+    /// extension of the path.
+    pub fn save(&self, map: &Map<f64>, path: impl AsRef<Path>) -> RasterResult<()> {
+        match RasterFormat::from_path(&path)? {
+            RasterFormat::Tiff => self.save_tiff(map, path),
+            RasterFormat::Exr => self.save_exr(map, path),
+            RasterFormat::Pfm => self.save_pfm(map, path),
+        }
+    }
+
+    /// Saves a map as a 32 bit floating point TIFF, with the metadata
+    /// in its image description.
+    pub fn save_tiff(&self, map: &Map<f64>, path: impl AsRef<Path>) -> RasterResult<()> {
+        let data: Vec<f32> = map.iter().map(|value| *value as f32).collect();
+        let mut encoder = TiffEncoder::new(BufWriter::new(File::create(path)?))?;
+        let mut image =
+            encoder.new_image::<Gray32Float>(map.width() as u32, map.height() as u32)?;
+
+        image
+            .encoder()
+            .write_tag(Tag::ImageDescription, self.metadata_text(map).as_str())?;
+        image.encoder().write_tag(Tag::Software, "ficture")?;
+        image.write_data(&data)?;
+
+        Ok(())
+    }
+
+    /// Saves a map as a 32 bit floating point OpenEXR image, with the
+    /// metadata as attributes of its layer.
+    pub fn save_exr(&self, map: &Map<f64>, path: impl AsRef<Path>) -> RasterResult<()> {
+        let data: Vec<f32> = map.iter().map(|value| *value as f32).collect();
+        let channel = AnyChannel::new("Y", FlatSamples::F32(data));
+        let mut attributes = LayerAttributes::named(self.name.as_str());
+
+        for (key, value) in self.metadata(map) {
+            attributes.other.insert(
+                Text::from(key.as_str()),
+                AttributeValue::Text(Text::from(value.as_str())),
+            );
+        }
+
+        let layer = Layer::new(
+            (map.width(), map.height()),
+            attributes,
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(std::iter::once(channel).collect()),
+        );
+
+        Image::from_layer(layer).write().to_file(path)?;
+
+        Ok(())
+    }
+
+    /// Saves a map as a grayscale portable float map, with the
+    /// metadata saved next to it in a file with a `.meta` extension.
+    pub fn save_pfm(&self, map: &Map<f64>, path: impl AsRef<Path>) -> RasterResult<()> {
+        let path = path.as_ref();
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        // a negative scale marks the data as little endian
+        write!(writer, "Pf\n{} {}\n-1.0\n", map.width(), map.height())?;
+
+        // rows are stored from the bottom of the image to the top
+        for y in (0..map.height()).rev() {
+            for x in 0..map.width() {
+                writer.write_all(&(*map.get(x, y) as f32).to_le_bytes())?;
+            }
+        }
+
+        writer.flush()?;
+        std::fs::write(path.with_extension("meta"), self.metadata_text(map))?;
+
+        Ok(())
+    }
+}
+
+/// A builder for the [`RasterExporter`].
+pub struct RasterExporterBuilder {
+    name: String,
+    parameters: Vec<(String, String)>,
+}
+
+impl RasterExporterBuilder {
+    /// Creates the [`RasterExporterBuilder`].
+    pub fn new() -> Self {
+        Self {
+            name: String::from("layer"),
+            parameters: vec![],
+        }
+    }
+
+    /// Sets the name of the layer being saved.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Adds a parameter used to generate the layer to the metadata.
+    pub fn parameter(mut self, key: impl Into<String>, value: impl ToString) -> Self {
+        self.parameters.push((key.into(), value.to_string()));
+        self
+    }
+
+    /// Construct the [`RasterExporter`] based on the
+    /// defined attributes.
+    pub fn build(self) -> RasterExporter {
+        RasterExporter {
+            name: self.name,
+            parameters: self.parameters,
+        }
+    }
+}
+
+impl Default for RasterExporterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(
+            RasterFormat::from_path("a/b.TIF").unwrap(),
+            RasterFormat::Tiff
+        );
+        assert_eq!(RasterFormat::from_path("b.exr").unwrap(), RasterFormat::Exr);
+        assert!(matches!(
+            RasterFormat::from_path("b.png"),
+            Err(RasterError::UnknownFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_pfm_is_stored_bottom_up() {
+        let map = Map::from_vec(vec![0.25, 0.5, 0.75, 1.0], 2, 2);
+        let exporter = RasterExporterBuilder::new()
+            .name("elevation")
+            .parameter("seed", 7)
+            .build();
+        let path =
+            std::env::temp_dir().join(format!("ficture_test_raster_{}.pfm", std::process::id()));
+
+        exporter.save_pfm(&map, &path).unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        let metadata = std::fs::read_to_string(path.with_extension("meta")).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(path.with_extension("meta")).unwrap();
+
+        let header = b"Pf\n2 2\n-1.0\n";
+        let first = f32::from_le_bytes(data[header.len()..header.len() + 4].try_into().unwrap());
+
+        assert!(data.starts_with(header));
+        assert_eq!(data.len(), header.len() + 16);
+        assert_eq!(first, 0.75);
+        assert_eq!(metadata, "layer=elevation\nmin=0.25\nmax=1\nseed=7\n");
+    }
+
+    #[test]
+    fn test_tiff_keeps_values_and_metadata() {
+        let map = Map::from_vec(vec![0.25, 0.5, 0.75, 1.0], 2, 2);
+        let exporter = RasterExporterBuilder::new()
+            .name("moisture")
+            .parameter("seed.noise_generators.moisture_noise", 7)
+            .build();
+        let path =
+            std::env::temp_dir().join(format!("ficture_test_raster_{}.tif", std::process::id()));
+
+        exporter.save_tiff(&map, &path).unwrap();
+
+        let mut decoder = tiff::decoder::Decoder::new(File::open(&path).unwrap()).unwrap();
+        let description = decoder.get_tag_ascii_string(Tag::ImageDescription).unwrap();
+        let data = decoder.read_image().unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            description,
+            "layer=moisture\nmin=0.25\nmax=1\nseed.noise_generators.moisture_noise=7\n"
+        );
+        assert!(matches!(
+            data,
+            tiff::decoder::DecodingResult::F32(data) if data == vec![0.25, 0.5, 0.75, 1.0]
+        ));
+    }
+}
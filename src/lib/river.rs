@@ -0,0 +1,356 @@
+//! This module provides functions for finding where water flows over
+//! the terrain and tracing the [`River`]s that it gathers into.
+//!
+//! Basins in the land are first filled up to where they overflow, so
+//! that water can always find its way to the ocean. Water in every
+//! land cell then flows to the neighbor that it drops to most steeply.
+//! The flow of a cell counts the cells that drain through it,
+//! including itself, and rivers are traced through every cell with
+//! enough flow. Rivers end where they reach the ocean or join another
+//! river. The map wraps from east to west.
+//!
+//! # Examples
+//!
+//! ```
+//! use ficture::map::{Map, MapMonad};
+//! use ficture::river::trace_rivers;
+//!
+//! // land rising away from the ocean along the north edge
+//! let elevation = Map::return_single(0.0, 4, 10);
+//! let elevation = elevation.and_then_with_coordinates(|_, _, y| y as f64 / 10.0);
+//! let rivers = trace_rivers(&elevation, 0.05, 5);
+//!
+//! assert_eq!(rivers.len(), 4);
+//! assert_eq!(rivers[0].flow, 9);
+//! ```
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use crate::map::Map;
+
+/// A river traced down the terrain from where it forms.
+#[derive(Debug, Clone, PartialEq)]
+pub struct River {
+    /// The centers of the cells that the river flows through, in the
+    /// direction that it flows, ending on the edge of the map where
+    /// it crosses it.
+    pub points: Vec<(f64, f64)>,
+    /// The number of cells that drain through the end of the river.
+    pub flow: usize,
+}
+
+/// The offsets of the neighbors of a cell, with the orthogonal ones
+/// first so that they win ties.
+const NEIGHBORS: [(isize, isize); 8] = [
+    (0, -1),
+    (1, 0),
+    (0, 1),
+    (-1, 0),
+    (1, -1),
+    (1, 1),
+    (-1, 1),
+    (-1, -1),
+];
+
+/// Gets the indices of the neighbors of a cell along with their
+/// distance from it and which way the step to them crosses the
+/// east-west edges of the map, which is -1 going west, 1 going east
+/// and 0 if it stays within the map. The map wraps from east to west.
+fn neighbors(
+    index: usize,
+    width: usize,
+    height: usize,
+) -> impl Iterator<Item = (usize, f64, isize)> {
+    let (x, y) = ((index % width) as isize, (index / width) as isize);
+
+    NEIGHBORS.into_iter().filter_map(move |(dx, dy)| {
+        let (nx, ny) = ((x + dx).rem_euclid(width as isize), y + dy);
+        let crossing = (x + dx).div_euclid(width as isize);
+        let distance = if dx != 0 && dy != 0 {
+            std::f64::consts::SQRT_2
+        } else {
+            1.0
+        };
+
+        (0..height as isize)
+            .contains(&ny)
+            .then(|| (ny as usize * width + nx as usize, distance, crossing))
+    })
+}
+
+/// A cell waiting to be filled, ordered so that the lowest cell comes
+/// out of a [`BinaryHeap`] first.
+#[derive(PartialEq)]
+struct Pending(f64, usize);
+
+impl Eq for Pending {}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0).then(other.1.cmp(&self.1))
+    }
+}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Raises the land of a map so that every land cell has a way down to
+/// the ocean, by filling basins up to where they overflow. Cells are
+/// filled outwards from the ocean, lowest first, and every cell is
+/// raised just above the cell that it was reached from, so that flat
+/// land still slopes towards the ocean. Maps without an ocean are left
+/// as they are.
+fn fill_basins(elevation: &Map<f64>, sea_level: f64) -> Vec<f64> {
+    let (width, height) = (elevation.width(), elevation.height());
+    let mut filled = elevation.to_vec();
+    let mut reached: Vec<bool> = filled.iter().map(|value| *value < sea_level).collect();
+    let mut pending: BinaryHeap<Pending> = (0..filled.len())
+        .filter(|index| reached[*index])
+        .map(|index| Pending(filled[index], index))
+        .collect();
+
+    while let Some(Pending(value, index)) = pending.pop() {
+        for (neighbor, _, _) in neighbors(index, width, height) {
+            if reached[neighbor] {
+                continue;
+            }
+
+            reached[neighbor] = true;
+            filled[neighbor] = filled[neighbor].max(value.next_up());
+            pending.push(Pending(filled[neighbor], neighbor));
+        }
+    }
+
+    filled
+}
+
+/// Gets the index of the cell that every land cell drains into, which
+/// is the neighbor that it drops to most steeply, along with which way
+/// the step crosses the east-west edges of the map. Cells in the ocean
+/// and cells without a lower neighbor don't drain anywhere.
+fn flow_directions(
+    filled: &[f64],
+    width: usize,
+    height: usize,
+    sea_level: f64,
+) -> Vec<Option<(usize, isize)>> {
+    (0..filled.len())
+        .map(|index| {
+            if filled[index] < sea_level {
+                return None;
+            }
+
+            neighbors(index, width, height)
+                .map(|(neighbor, distance, crossing)| {
+                    let slope = (filled[index] - filled[neighbor]) / distance;
+
+                    (neighbor, crossing, slope)
+                })
+                .filter(|(_, _, slope)| *slope > 0.0)
+                .fold(
+                    None,
+                    |steepest, (neighbor, crossing, slope)| match steepest {
+                        Some((_, _, steepest_slope)) if steepest_slope >= slope => steepest,
+                        _ => Some((neighbor, crossing, slope)),
+                    },
+                )
+                .map(|(neighbor, crossing, _)| (neighbor, crossing))
+        })
+        .collect()
+}
+
+/// Counts how many cells drain through every cell, given the cell
+/// that each cell drains into.
+fn accumulate(filled: &[f64], directions: &[Option<(usize, isize)>]) -> Vec<usize> {
+    let mut flow = vec![1; filled.len()];
+    let mut order: Vec<usize> = (0..filled.len()).collect();
+
+    // water only flows downhill, so passing it on from the highest
+    // cell to the lowest passes on everything upstream of each cell
+    order.sort_by(|a, b| filled[*b].total_cmp(&filled[*a]));
+
+    for index in order {
+        if let Some((next, _)) = directions[index] {
+            flow[next] += flow[index];
+        }
+    }
+
+    flow
+}
+
+/// Counts how many cells drain through every cell of a map,
+/// including the cell itself.
+pub fn flow_accumulation(elevation: &Map<f64>, sea_level: f64) -> Map<usize> {
+    let (width, height) = (elevation.width(), elevation.height());
+    let filled = fill_basins(elevation, sea_level);
+    let directions = flow_directions(&filled, width, height, sea_level);
+
+    Map::from_vec(accumulate(&filled, &directions), width, height)
+}
+
+/// Traces the rivers of a map through every land cell that at least
+/// `min_flow` cells drain through. Rivers continue into the ocean cell
+/// that they flow into so that they meet the coastline. Rivers that
+/// cross the east-west edges of the map are split there.
+pub fn trace_rivers(elevation: &Map<f64>, sea_level: f64, min_flow: usize) -> Vec<River> {
+    let (width, height) = (elevation.width(), elevation.height());
+    let filled = fill_basins(elevation, sea_level);
+    let directions = flow_directions(&filled, width, height, sea_level);
+    let flow = accumulate(&filled, &directions);
+    let is_river = |index: usize| filled[index] >= sea_level && flow[index] >= min_flow;
+    let mut fed = vec![false; elevation.len()];
+
+    for index in (0..elevation.len()).filter(|index| is_river(*index)) {
+        if let Some((next, _)) = directions[index] {
+            fed[next] = true;
+        }
+    }
+
+    // rivers start where no other river flows in, and the highest
+    // are traced first so that they keep the longest course
+    let mut sources: Vec<usize> = (0..elevation.len())
+        .filter(|index| is_river(*index) && !fed[*index])
+        .collect();
+
+    sources.sort_by(|a, b| filled[*b].total_cmp(&filled[*a]));
+
+    let mut visited = vec![false; elevation.len()];
+    let mut rivers = vec![];
+
+    for source in sources {
+        let mut cells = vec![source];
+        let mut crossings = vec![];
+        let mut cell = source;
+
+        visited[source] = true;
+
+        while let Some((next, crossing)) = directions[cell] {
+            cells.push(next);
+            crossings.push(crossing);
+
+            if visited[next] || filled[next] < sea_level {
+                break;
+            }
+
+            visited[next] = true;
+            cell = next;
+        }
+
+        if cells.len() < 2 {
+            continue;
+        }
+
+        let center = |index: usize| ((index % width) as f64 + 0.5, (index / width) as f64 + 0.5);
+        let mut points = vec![center(cells[0])];
+
+        for (pair, crossing) in cells.windows(2).zip(crossings) {
+            let (from, to) = (center(pair[0]), center(pair[1]));
+
+            // a step across the edge of the map ends the river there
+            // and continues it from the other edge
+            if crossing != 0 {
+                let y = (from.1 + to.1) / 2.0;
+                let (end, start) = if crossing > 0 {
+                    (width as f64, 0.0)
+                } else {
+                    (0.0, width as f64)
+                };
+
+                points.push((end, y));
+                rivers.push(River {
+                    points,
+                    flow: flow[cell],
+                });
+                points = vec![(start, y)];
+            }
+
+            points.push(to);
+        }
+
+        rivers.push(River {
+            points,
+            flow: flow[cell],
+        });
+    }
+
+    rivers
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::map::MapMonad;
+
+    #[test]
+    fn test_valley_gathers_into_one_river() {
+        let elevation = Map::return_single(0.0, 5, 6).and_then_with_coordinates(|_, x, y| {
+            if y == 0 {
+                0.0
+            } else {
+                (x as f64 - 2.0).abs() * 0.2 + y as f64 * 0.05 + 0.1
+            }
+        });
+        let rivers = trace_rivers(&elevation, 0.05, 3);
+
+        // the cells beside the river mouth drain straight into the ocean
+        assert_eq!(*flow_accumulation(&elevation, 0.05).get(2, 1), 21);
+        assert_eq!(rivers.len(), 1);
+        assert_eq!(rivers[0].flow, 21);
+        assert_eq!(rivers[0].points.first(), Some(&(2.5, 5.5)));
+        assert_eq!(rivers[0].points.last(), Some(&(2.5, 0.5)));
+    }
+
+    #[test]
+    fn test_rivers_are_split_at_the_edges() {
+        let elevation = Map::from_vec(vec![0.1, 0.0, 0.5, 0.4, 0.3, 0.2], 6, 1);
+        let rivers = trace_rivers(&elevation, 0.05, 2);
+
+        assert_eq!(
+            rivers,
+            vec![
+                River {
+                    points: vec![(4.5, 0.5), (5.5, 0.5), (6.0, 0.5)],
+                    flow: 4,
+                },
+                River {
+                    points: vec![(0.0, 0.5), (0.5, 0.5), (1.5, 0.5)],
+                    flow: 4,
+                },
+            ]
+        );
+
+        // on a map two cells wide, the step across the edge is no
+        // longer than the step within the map
+        let elevation = Map::from_vec(vec![0.0, 0.5], 2, 1);
+
+        assert_eq!(
+            trace_rivers(&elevation, 0.05, 1),
+            vec![
+                River {
+                    points: vec![(1.5, 0.5), (2.0, 0.5)],
+                    flow: 1,
+                },
+                River {
+                    points: vec![(0.0, 0.5), (0.5, 0.5)],
+                    flow: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rivers_flow_out_of_basins() {
+        let elevation = Map::from_vec(vec![0.0, 0.3, 0.1, 0.2, 0.5, 0.0], 6, 1);
+        let rivers = trace_rivers(&elevation, 0.05, 2);
+
+        assert_eq!(
+            rivers,
+            vec![River {
+                points: vec![(2.5, 0.5), (1.5, 0.5), (0.5, 0.5)],
+                flow: 3,
+            }]
+        );
+    }
+}
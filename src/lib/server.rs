@@ -0,0 +1,577 @@
+//! This module provides a [`TileServer`] for exploring a map in a
+//! browser, rendering tiles on demand as they are panned and zoomed
+//! to rather than saving them ahead of time.
+//!
+//! The server answers plain HTTP requests on a [`TcpListener`]. It
+//! serves the tile viewer at `/`, tiles at `/z/x/y.png` and a version
+//! number at `/version`. Rendered tiles are kept in an [`LruCache`].
+//! The config file is checked for changes on every request, and when
+//! it has changed it is loaded again, the cache is cleared and the
+//! version is bumped so that open viewers reload their tiles. Reloads
+//! and failed requests are reported as [`ServerEvent`]s to a handler
+//! set with [`TileServerBuilder::on_event`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::net::TcpListener;
+//!
+//! use ficture::server::TileServerBuilder;
+//!
+//! let server = TileServerBuilder::new("config/config.yaml")
+//!     .seed(Some(42))
+//!     .max_zoom(6)
+//!     .on_event(|event| eprintln!("{event}"))
+//!     .build();
+//! let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
+//!
+//! server.serve(listener).unwrap();
+//! ```
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    io::{BufRead, BufReader, Cursor, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use image::ImageOutputFormat;
+use thiserror::Error;
+
+use crate::{
+    config::{Config, ConfigError},
+    tiles::{viewer_html, TileError, TileLevel, TileRenderer},
+    utils::wrap,
+};
+
+/// The error type for server errors.
+#[derive(Error, Debug)]
+pub enum ServerError {
+    #[error("failed to serve request: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to load config: {0}")]
+    Config(#[from] ConfigError),
+    #[error("failed to render tile: {0}")]
+    Tile(#[from] TileError),
+    #[error("failed to encode tile: {0}")]
+    Image(#[from] image::ImageError),
+}
+
+/// A result type for [`ServerError`].
+pub type ServerResult<T> = Result<T, ServerError>;
+
+/// How long a connection may take to send its request before it is
+/// dropped, so that idle connections don't hold on to their threads.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Something that happened while serving tiles.
+#[derive(Error, Debug)]
+pub enum ServerEvent {
+    #[error("reloaded {0}")]
+    Reloaded(PathBuf),
+    #[error("failed to reload {0}: {1}")]
+    ReloadFailed(PathBuf, ServerError),
+    #[error("{0}")]
+    RequestFailed(ServerError),
+}
+
+/// A type for a function that is told about the events of a
+/// [`TileServer`].
+pub type EventHandler = Box<dyn Fn(ServerEvent) + Send + Sync>;
+
+/// A cache that holds up to a fixed number of values, dropping the
+/// least recently used value to make room for new ones. Values are
+/// ordered by when they were last used, so that finding the least
+/// recently used value doesn't scan the whole cache.
+pub struct LruCache<K, V> {
+    capacity: usize,
+    entries: HashMap<K, (V, u64)>,
+    recency: BTreeMap<u64, K>,
+    tick: u64,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> LruCache<K, V> {
+    /// Creates an empty [`LruCache`] holding up to `capacity` values.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Gets a value from the cache, marking it as recently used.
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        self.tick += 1;
+
+        let tick = self.tick;
+        let (value, used) = self.entries.get_mut(key)?;
+
+        self.recency.remove(used);
+        self.recency.insert(tick, key.clone());
+        *used = tick;
+
+        Some(value.clone())
+    }
+
+    /// Adds a value to the cache, dropping the least recently used
+    /// value if the cache is full.
+    pub fn insert(&mut self, key: K, value: V) {
+        if let Some((_, used)) = self.entries.get(&key) {
+            self.recency.remove(used);
+        } else if self.entries.len() >= self.capacity {
+            if let Some((_, oldest)) = self.recency.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.tick += 1;
+        self.recency.insert(self.tick, key.clone());
+        self.entries.insert(key, (value, self.tick));
+    }
+
+    /// Removes every value from the cache.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    /// The number of values in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Parses the zoom level, column and row from the path of a tile,
+/// such as `/3/5/2.png`.
+fn parse_tile_path(path: &str) -> Option<(u32, i64, i64)> {
+    let mut parts = path.strip_prefix('/')?.strip_suffix(".png")?.split('/');
+    let zoom = parts.next()?.parse().ok()?;
+    let x = parts.next()?.parse().ok()?;
+    let y = parts.next()?.parse().ok()?;
+
+    match parts.next() {
+        Some(_) => None,
+        None => Some((zoom, x, y)),
+    }
+}
+
+/// Writes an HTTP response to a stream and closes the connection.
+fn respond(
+    mut stream: &TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+/// Everything the server shares between requests.
+struct State {
+    renderer: Arc<TileRenderer>,
+    levels: HashMap<u32, Arc<TileLevel>>,
+    tiles: LruCache<(u32, i64, i64), Arc<Vec<u8>>>,
+    modified: Option<SystemTime>,
+    version: u64,
+}
+
+/// A structure for serving the tiles of a map over HTTP.
+pub struct TileServer {
+    config_path: PathBuf,
+    seed: Option<u32>,
+    sea_level: f64,
+    tile_size: usize,
+    max_zoom: u32,
+    cache_size: usize,
+    on_event: EventHandler,
+}
+
+impl TileServer {
+    /// Loads the config file and creates a renderer from it.
+    fn load(&self) -> ServerResult<TileRenderer> {
+        let mut config = Config::from_file(self.config_path.to_string_lossy())?;
+
+        config.validate()?;
+
+        if let Some(seed) = self.seed {
+            config.reseed(seed);
+        }
+
+        Ok(TileRenderer::new(config, self.sea_level, self.tile_size)?)
+    }
+
+    /// Gets when the config file was last changed.
+    fn modified(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.config_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    /// Loads the config file again if it has changed since it was
+    /// last loaded. A config that fails to load is reported and the
+    /// last config is kept, so that a half written file doesn't stop
+    /// the server.
+    fn refresh(&self, state: &Mutex<State>) {
+        let modified = self.modified();
+
+        {
+            let mut state = state.lock().unwrap();
+
+            if modified == state.modified {
+                return;
+            }
+
+            state.modified = modified;
+        }
+
+        // the config is loaded without holding the lock so that tiles
+        // can still be served from the last config in the meantime
+        match self.load() {
+            Ok(renderer) => {
+                let mut state = state.lock().unwrap();
+
+                state.renderer = Arc::new(renderer);
+                state.levels.clear();
+                state.tiles.clear();
+                state.version += 1;
+                drop(state);
+                (self.on_event)(ServerEvent::Reloaded(self.config_path.clone()));
+            }
+            Err(error) => {
+                (self.on_event)(ServerEvent::ReloadFailed(self.config_path.clone(), error))
+            }
+        }
+    }
+
+    /// Gets a tile as a PNG, rendering it if it isn't cached. Returns
+    /// `None` for tiles outside of the world.
+    fn tile(
+        &self,
+        state: &Mutex<State>,
+        zoom: u32,
+        x: i64,
+        y: i64,
+    ) -> ServerResult<Option<Arc<Vec<u8>>>> {
+        if zoom > self.max_zoom {
+            return Ok(None);
+        }
+
+        // the lock is released while building levels and rendering so
+        // that tiles can be rendered at the same time
+        let (renderer, level, version, key) = {
+            let mut state = state.lock().unwrap();
+            let (columns, _) = state.renderer.tile_counts(zoom);
+            let key = (zoom, wrap(x as isize, columns) as i64, y);
+
+            if let Some(tile) = state.tiles.get(&key) {
+                return Ok(Some(tile));
+            }
+
+            (
+                state.renderer.clone(),
+                state.levels.get(&zoom).cloned(),
+                state.version,
+                key,
+            )
+        };
+        let level = match level {
+            Some(level) => level,
+            None => {
+                let level = Arc::new(renderer.level(zoom)?);
+                let mut state = state.lock().unwrap();
+
+                // a level built from a config that has since been
+                // reloaded is used once but not kept
+                if state.version == version {
+                    state.levels.insert(zoom, level.clone());
+                }
+
+                level
+            }
+        };
+        let Some(image) = renderer.render_tile(&level, key.1, key.2) else {
+            return Ok(None);
+        };
+        let mut png = Cursor::new(vec![]);
+
+        image.write_to(&mut png, ImageOutputFormat::Png)?;
+
+        let png = Arc::new(png.into_inner());
+        let mut state = state.lock().unwrap();
+
+        // a tile rendered from a config that has since been reloaded
+        // is stale, so it is served once but not cached
+        if state.version == version {
+            state.tiles.insert(key, png.clone());
+        }
+
+        Ok(Some(png))
+    }
+
+    /// Answers a single request.
+    fn handle(&self, stream: TcpStream, state: &Mutex<State>) -> ServerResult<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+        let mut reader = BufReader::new(&stream);
+        let mut request = String::new();
+
+        reader.read_line(&mut request)?;
+
+        // the headers aren't needed, but are read so that the client
+        // isn't cut off while sending them
+        loop {
+            let mut header = String::new();
+
+            if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+        }
+
+        let mut parts = request.split_whitespace();
+        let method = parts.next().unwrap_or_default();
+        let path = parts
+            .next()
+            .unwrap_or_default()
+            .split('?')
+            .next()
+            .unwrap_or_default();
+
+        if method != "GET" {
+            respond(&stream, "405 Method Not Allowed", "text/plain", b"")?;
+            return Ok(());
+        }
+
+        self.refresh(state);
+
+        match path {
+            "/" | "/index.html" => {
+                let html = viewer_html(self.tile_size, self.max_zoom, true);
+
+                respond(&stream, "200 OK", "text/html", html.as_bytes())?;
+            }
+            "/version" => {
+                let version = state.lock().unwrap().version.to_string();
+
+                respond(&stream, "200 OK", "text/plain", version.as_bytes())?;
+            }
+            path => match parse_tile_path(path) {
+                Some((zoom, x, y)) => match self.tile(state, zoom, x, y) {
+                    Ok(Some(png)) => respond(&stream, "200 OK", "image/png", &png)?,
+                    Ok(None) => respond(&stream, "404 Not Found", "text/plain", b"")?,
+                    Err(error) => {
+                        let message = error.to_string();
+
+                        respond(
+                            &stream,
+                            "500 Internal Server Error",
+                            "text/plain",
+                            message.as_bytes(),
+                        )?;
+                        return Err(error);
+                    }
+                },
+                None => respond(&stream, "404 Not Found", "text/plain", b"")?,
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Serves tiles to every connection accepted by `listener`,
+    /// answering each connection on its own thread. This only returns
+    /// if the config can't be loaded when starting.
+    pub fn serve(&self, listener: TcpListener) -> ServerResult<()> {
+        let modified = self.modified();
+        let state = Mutex::new(State {
+            renderer: Arc::new(self.load()?),
+            levels: HashMap::new(),
+            tiles: LruCache::new(self.cache_size),
+            modified,
+            version: 0,
+        });
+
+        std::thread::scope(|scope| {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let state = &state;
+
+                scope.spawn(move || {
+                    if let Err(error) = self.handle(stream, state) {
+                        (self.on_event)(ServerEvent::RequestFailed(error));
+                    }
+                });
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// A builder for the [`TileServer`].
+pub struct TileServerBuilder {
+    config_path: PathBuf,
+    seed: Option<u32>,
+    sea_level: f64,
+    tile_size: usize,
+    max_zoom: u32,
+    cache_size: usize,
+    on_event: EventHandler,
+}
+
+impl TileServerBuilder {
+    /// Creates the [`TileServerBuilder`] for the config file at
+    /// `config_path`.
+    pub fn new(config_path: impl Into<PathBuf>) -> Self {
+        Self {
+            config_path: config_path.into(),
+            seed: None,
+            sea_level: 0.0,
+            tile_size: 256,
+            max_zoom: 8,
+            cache_size: 1024,
+            on_event: Box::new(|_| {}),
+        }
+    }
+
+    /// Sets the seed that replaces the seeds of the config file.
+    pub fn seed(mut self, seed: Option<u32>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets the elevation of the sea level.
+    pub fn sea_level(mut self, sea_level: f64) -> Self {
+        self.sea_level = sea_level;
+        self
+    }
+
+    /// Sets the width and height of every tile in pixels.
+    pub fn tile_size(mut self, tile_size: usize) -> Self {
+        self.tile_size = tile_size;
+        self
+    }
+
+    /// Sets the highest zoom level that tiles are rendered for.
+    pub fn max_zoom(mut self, max_zoom: u32) -> Self {
+        self.max_zoom = max_zoom;
+        self
+    }
+
+    /// Sets how many rendered tiles are cached.
+    pub fn cache_size(mut self, cache_size: usize) -> Self {
+        self.cache_size = cache_size;
+        self
+    }
+
+    /// Sets the function that is told about reloads of the config
+    /// file and requests that fail. Events are ignored by default.
+    pub fn on_event(mut self, on_event: impl Fn(ServerEvent) + Send + Sync + 'static) -> Self {
+        self.on_event = Box::new(on_event);
+        self
+    }
+
+    /// Construct the [`TileServer`] based on the
+    /// defined attributes.
+    pub fn build(self) -> TileServer {
+        TileServer {
+            config_path: self.config_path,
+            seed: self.seed,
+            sea_level: self.sea_level,
+            tile_size: self.tile_size,
+            max_zoom: self.max_zoom,
+            cache_size: self.cache_size,
+            on_event: self.on_event,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cache_drops_least_recently_used() {
+        let mut cache = LruCache::new(2);
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        assert_eq!(cache.get(&"a"), Some(1));
+
+        cache.insert("c", 3);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"c"), Some(3));
+
+        // replacing a value marks it as used without dropping another
+        cache.insert("a", 4);
+        cache.insert("d", 5);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"c"), None);
+        assert_eq!(cache.get(&"a"), Some(4));
+    }
+
+    #[test]
+    fn test_failed_reload_is_reported_and_keeps_config() {
+        let path =
+            std::env::temp_dir().join(format!("ficture_test_server_{}.yaml", std::process::id()));
+
+        std::fs::copy("config/config.yaml", &path).unwrap();
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let server = TileServerBuilder::new(&path)
+            .tile_size(16)
+            .on_event({
+                let events = events.clone();
+
+                move |event| events.lock().unwrap().push(event.to_string())
+            })
+            .build();
+        let state = Mutex::new(State {
+            renderer: Arc::new(server.load().unwrap()),
+            levels: HashMap::new(),
+            tiles: LruCache::new(4),
+            modified: None,
+            version: 0,
+        });
+
+        std::fs::write(&path, "noise_generators: [").unwrap();
+        server.refresh(&state);
+        std::fs::remove_file(&path).unwrap();
+
+        let tile = server.tile(&state, 0, 0, 0).unwrap();
+        let state = state.lock().unwrap();
+
+        assert!(tile.is_some());
+        assert_eq!(state.version, 0);
+        assert!(state.levels.contains_key(&0));
+        assert_eq!(events.lock().unwrap().len(), 1);
+        assert!(events.lock().unwrap()[0].starts_with("failed to reload"));
+    }
+
+    #[test]
+    fn test_parse_tile_path() {
+        assert_eq!(parse_tile_path("/3/-1/2.png"), Some((3, -1, 2)));
+        assert_eq!(parse_tile_path("/3/1.png"), None);
+        assert_eq!(parse_tile_path("/3/1/2/4.png"), None);
+        assert_eq!(parse_tile_path("/index.html"), None);
+    }
+}
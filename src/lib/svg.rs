@@ -0,0 +1,504 @@
+//! This module provides an [`SvgExporter`] for saving maps as vector
+//! images, for printing fantasy maps at any size.
+//!
+//! Every biome is traced into polygons along the edges of its cells
+//! and filled with a color, usually taken from the gradient of the
+//! biome in the config file. The ocean fills the background, and any
+//! contours, rivers and the coastline are drawn over the biomes as
+//! paths, with wider rivers where more water flows.
+//! Every biome is given a class named after it, so that the styling
+//! can be changed afterwards with CSS.
+//!
+//! Coordinates are measured in cells, where the cell at `(x, y)`
+//! covers the square from `(x, y)` to `(x + 1, y + 1)`, and are
+//! scaled by the scale of the exporter.
+//!
+//! # Examples
+//!
+//! ```
+//! use image::Rgba;
+//!
+//! use ficture::biome::BiomeId;
+//! use ficture::map::{Map, MapMonad};
+//! use ficture::svg::SvgExporterBuilder;
+//!
+//! let elevation = Map::return_single(0.0, 10, 10);
+//! let elevation = elevation.and_then_with_coordinates(|_, x, _| x as f64 / 10.0);
+//! let biomes = Map::from_vec(
+//!     elevation
+//!         .iter()
+//!         .map(|elevation| if *elevation < 0.5 { BiomeId::NONE } else { BiomeId(1) })
+//!         .collect(),
+//!     10,
+//!     10,
+//! );
+//!
+//! let exporter = SvgExporterBuilder::new()
+//!     .sea_level(0.5)
+//!     .biome(BiomeId(1), "grassland", Rgba([90, 160, 70, 255]))
+//!     .build();
+//! let svg = exporter.to_svg(&biomes, &elevation, &[], &[]);
+//!
+//! assert!(svg.contains("class=\"biome-grassland\""));
+//! ```
+use std::{collections::BTreeMap, path::Path};
+
+use image::Rgba;
+
+use crate::{
+    biome::BiomeId,
+    contour::{trace_contours, ContourLine, Polyline},
+    map::Map,
+    river::River,
+};
+
+/// A closed ring of points on the corners of cells.
+pub type Ring = Vec<(usize, usize)>;
+
+/// The edges along the boundary of a region, from the corner each
+/// edge starts at to the corners that they end at.
+type Edges = BTreeMap<(usize, usize), Vec<(usize, usize)>>;
+
+/// Traces the regions of every biome of a map into rings along the
+/// edges of their cells. Rings run clockwise around the biome and
+/// counterclockwise around holes in it, so the rings of a biome
+/// should be filled with the even-odd rule. Regions are not joined
+/// across the east-west edges of the map.
+pub fn trace_regions(map: &Map<BiomeId>) -> BTreeMap<BiomeId, Vec<Ring>> {
+    let (width, height) = (map.width(), map.height());
+    let mut edges: BTreeMap<BiomeId, Edges> = BTreeMap::new();
+
+    for y in 0..height {
+        for x in 0..width {
+            let id = *map.get(x, y);
+            let differs = |x: isize, y: isize| {
+                x < 0
+                    || y < 0
+                    || x >= width as isize
+                    || y >= height as isize
+                    || *map.get(x as usize, y as usize) != id
+            };
+            let (cx, cy) = (x as isize, y as isize);
+            let sides = [
+                (differs(cx, cy - 1), (x, y), (x + 1, y)),
+                (differs(cx + 1, cy), (x + 1, y), (x + 1, y + 1)),
+                (differs(cx, cy + 1), (x + 1, y + 1), (x, y + 1)),
+                (differs(cx - 1, cy), (x, y + 1), (x, y)),
+            ];
+            let edges = edges.entry(id).or_default();
+
+            for (boundary, start, end) in sides {
+                if boundary {
+                    edges.entry(start).or_default().push(end);
+                }
+            }
+        }
+    }
+
+    edges
+        .into_iter()
+        .map(|(id, mut edges)| {
+            let mut rings = vec![];
+
+            // every corner has as many edges leaving it as entering
+            // it, so following edges always leads back to the start
+            while let Some(&start) = edges.keys().next() {
+                let mut ring = vec![start];
+                let mut corner = start;
+
+                loop {
+                    let next = edges.get_mut(&corner).and_then(|ends| ends.pop());
+
+                    if edges.get(&corner).is_some_and(|ends| ends.is_empty()) {
+                        edges.remove(&corner);
+                    }
+
+                    match next {
+                        Some(next) if next != start => {
+                            ring.push(next);
+                            corner = next;
+                        }
+                        _ => break,
+                    }
+                }
+
+                rings.push(simplify_ring(ring));
+            }
+
+            (id, rings)
+        })
+        .collect()
+}
+
+/// Removes the corners of a ring that lie on a straight line between
+/// their neighbors.
+fn simplify_ring(ring: Ring) -> Ring {
+    let len = ring.len();
+
+    (0..len)
+        .filter(|&i| {
+            let (previous, corner, next) =
+                (ring[(i + len - 1) % len], ring[i], ring[(i + 1) % len]);
+
+            !((previous.0 == corner.0 && corner.0 == next.0)
+                || (previous.1 == corner.1 && corner.1 == next.1))
+        })
+        .map(|i| ring[i])
+        .collect()
+}
+
+/// Formats a color as an SVG paint for `attribute`, along with its
+/// opacity if it is transparent.
+fn paint(attribute: &str, color: Rgba<u8>) -> String {
+    let [r, g, b, a] = color.0;
+    let paint = format!("{attribute}=\"#{r:02x}{g:02x}{b:02x}\"");
+
+    if a == 255 {
+        paint
+    } else {
+        format!("{paint} {attribute}-opacity=\"{:.3}\"", a as f64 / 255.0)
+    }
+}
+
+/// Turns a name into a class name, replacing anything other than
+/// letters and digits with dashes.
+fn class_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// Formats a line through points as SVG path data.
+fn line_data(points: &[(f64, f64)]) -> String {
+    let mut data = String::new();
+
+    for (i, (x, y)) in points.iter().enumerate() {
+        let command = if i == 0 { 'M' } else { 'L' };
+
+        data.push_str(&format!("{command}{x:.2} {y:.2}"));
+    }
+
+    data
+}
+
+/// Formats polylines as the data of a single SVG path.
+fn polyline_data(polylines: &[Polyline]) -> String {
+    let mut data = String::new();
+
+    for polyline in polylines {
+        data.push_str(&line_data(&polyline.points));
+        if polyline.closed {
+            data.push('Z');
+        }
+    }
+
+    data
+}
+
+/// A structure for saving maps as SVG images.
+pub struct SvgExporter {
+    scale: f64,
+    sea_level: f64,
+    ocean: Rgba<u8>,
+    biomes: Vec<(BiomeId, String, Rgba<u8>)>,
+    coastline: Rgba<u8>,
+    coastline_width: f64,
+    contour_color: Rgba<u8>,
+    major_contour_color: Rgba<u8>,
+    river: Rgba<u8>,
+    river_width: f64,
+}
+
+impl SvgExporter {
+    /// Creates an SVG image of a map from the biome of every cell,
+    /// the elevation of every cell and any contours and rivers of the
+    /// map. Cells in biomes without a fill are left as ocean.
+    pub fn to_svg(
+        &self,
+        biomes: &Map<BiomeId>,
+        elevation: &Map<f64>,
+        contours: &[ContourLine],
+        rivers: &[River],
+    ) -> String {
+        let (width, height) = (biomes.width(), biomes.height());
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {width} {height}\">\n",
+            width as f64 * self.scale,
+            height as f64 * self.scale,
+        );
+
+        svg.push_str(&format!(
+            "<rect class=\"ocean\" width=\"{width}\" height=\"{height}\" {}/>\n",
+            paint("fill", self.ocean)
+        ));
+        svg.push_str("<g class=\"biomes\" fill-rule=\"evenodd\" stroke-width=\"0.5\" stroke-linejoin=\"round\">\n");
+
+        let regions = trace_regions(biomes);
+
+        for (id, name, fill) in &self.biomes {
+            let Some(rings) = regions.get(id) else {
+                continue;
+            };
+            let mut data = String::new();
+
+            for ring in rings {
+                for (i, (x, y)) in ring.iter().enumerate() {
+                    let command = if i == 0 { 'M' } else { 'L' };
+
+                    data.push_str(&format!("{command}{x} {y}"));
+                }
+                data.push('Z');
+            }
+
+            // the stroke covers the hairline seams that renderers
+            // leave between neighboring polygons
+            svg.push_str(&format!(
+                "<path class=\"biome-{}\" data-biome=\"{}\" {} {} d=\"{data}\"/>\n",
+                class_name(name),
+                id.0,
+                paint("fill", *fill),
+                paint("stroke", *fill)
+            ));
+        }
+
+        svg.push_str("</g>\n");
+        svg.push_str("<g class=\"contours\" fill=\"none\" stroke-linejoin=\"round\">\n");
+
+        // the contour at sea level is drawn as the coastline
+        for contour in contours {
+            if contour.polylines.is_empty() || (contour.level - self.sea_level).abs() < 1e-9 {
+                continue;
+            }
+
+            let (color, stroke_width) = if contour.major {
+                (self.major_contour_color, 0.5)
+            } else {
+                (self.contour_color, 0.25)
+            };
+
+            svg.push_str(&format!(
+                "<path class=\"{}\" data-level=\"{}\" {} stroke-width=\"{stroke_width}\" d=\"{}\"/>\n",
+                if contour.major { "major" } else { "minor" },
+                contour.level,
+                paint("stroke", color),
+                polyline_data(&contour.polylines)
+            ));
+        }
+
+        svg.push_str("</g>\n");
+        svg.push_str(&format!(
+            "<g class=\"rivers\" fill=\"none\" stroke-linecap=\"round\" stroke-linejoin=\"round\" {}>\n",
+            paint("stroke", self.river)
+        ));
+
+        let max_flow = rivers.iter().map(|river| river.flow).max().unwrap_or(1);
+
+        // rivers widen with the square root of their flow, up to the
+        // width of the river with the most flow
+        for river in rivers {
+            let stroke_width =
+                self.river_width * (river.flow as f64 / max_flow as f64).sqrt().max(0.2);
+
+            svg.push_str(&format!(
+                "<path data-flow=\"{}\" stroke-width=\"{stroke_width:.3}\" d=\"{}\"/>\n",
+                river.flow,
+                line_data(&river.points)
+            ));
+        }
+
+        svg.push_str("</g>\n");
+        svg.push_str(&format!(
+            "<path class=\"coastline\" fill=\"none\" stroke-linejoin=\"round\" {} stroke-width=\"{}\" d=\"{}\"/>\n",
+            paint("stroke", self.coastline),
+            self.coastline_width,
+            polyline_data(&trace_contours(elevation, self.sea_level))
+        ));
+        svg.push_str("</svg>\n");
+
+        svg
+    }
+
+    /// Saves an SVG image of a map. See [`SvgExporter::to_svg`].
+    pub fn save(
+        &self,
+        biomes: &Map<BiomeId>,
+        elevation: &Map<f64>,
+        contours: &[ContourLine],
+        rivers: &[River],
+        path: impl AsRef<Path>,
+    ) -> std::io::Result<()> {
+        std::fs::write(path, self.to_svg(biomes, elevation, contours, rivers))
+    }
+}
+
+/// A builder for the [`SvgExporter`].
+pub struct SvgExporterBuilder {
+    scale: f64,
+    sea_level: f64,
+    ocean: Rgba<u8>,
+    biomes: Vec<(BiomeId, String, Rgba<u8>)>,
+    coastline: Rgba<u8>,
+    coastline_width: f64,
+    contour_color: Rgba<u8>,
+    major_contour_color: Rgba<u8>,
+    river: Rgba<u8>,
+    river_width: f64,
+}
+
+impl SvgExporterBuilder {
+    /// Creates the [`SvgExporterBuilder`].
+    pub fn new() -> Self {
+        Self {
+            scale: 1.0,
+            sea_level: 0.0,
+            ocean: Rgba([38, 84, 148, 255]),
+            biomes: vec![],
+            coastline: Rgba([20, 30, 40, 255]),
+            coastline_width: 1.0,
+            contour_color: Rgba([0, 0, 0, 64]),
+            major_contour_color: Rgba([0, 0, 0, 128]),
+            river: Rgba([38, 84, 148, 255]),
+            river_width: 1.0,
+        }
+    }
+
+    /// Sets how many units wide each cell is in the image.
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Sets the elevation of the sea level, which the coastline is
+    /// traced at.
+    pub fn sea_level(mut self, sea_level: f64) -> Self {
+        self.sea_level = sea_level;
+        self
+    }
+
+    /// Sets the color that the ocean is filled with.
+    pub fn ocean(mut self, ocean: Rgba<u8>) -> Self {
+        self.ocean = ocean;
+        self
+    }
+
+    /// Adds a biome to draw, along with its name and the color that
+    /// it is filled with.
+    pub fn biome(mut self, id: BiomeId, name: impl Into<String>, fill: Rgba<u8>) -> Self {
+        self.biomes.push((id, name.into(), fill));
+        self
+    }
+
+    /// Sets the color and width of the coastline, in cells.
+    pub fn coastline(mut self, color: Rgba<u8>, width: f64) -> Self {
+        self.coastline = color;
+        self.coastline_width = width;
+        self
+    }
+
+    /// Sets the colors of the minor and major contours.
+    pub fn contour_colors(mut self, color: Rgba<u8>, major_color: Rgba<u8>) -> Self {
+        self.contour_color = color;
+        self.major_contour_color = major_color;
+        self
+    }
+
+    /// Sets the color of rivers and the width, in cells, of the river
+    /// with the most flow.
+    pub fn river(mut self, color: Rgba<u8>, width: f64) -> Self {
+        self.river = color;
+        self.river_width = width;
+        self
+    }
+
+    /// Construct the [`SvgExporter`] based on the
+    /// defined attributes.
+    pub fn build(self) -> SvgExporter {
+        SvgExporter {
+            scale: self.scale,
+            sea_level: self.sea_level,
+            ocean: self.ocean,
+            biomes: self.biomes,
+            coastline: self.coastline,
+            coastline_width: self.coastline_width,
+            contour_color: self.contour_color,
+            major_contour_color: self.major_contour_color,
+            river: self.river,
+            river_width: self.river_width,
+        }
+    }
+}
+
+impl Default for SvgExporterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_regions_keep_holes() {
+        let (a, b) = (BiomeId(1), BiomeId(2));
+        let map = Map::from_vec(vec![a, a, a, a, b, a, a, a, a], 3, 3);
+        let regions = trace_regions(&map);
+        let mut outer = regions[&a].clone();
+
+        outer.sort_by_key(|ring| ring.iter().max().copied());
+
+        assert_eq!(regions[&b], vec![vec![(1, 1), (2, 1), (2, 2), (1, 2)]]);
+        assert_eq!(outer.len(), 2);
+        assert!(outer.iter().all(|ring| ring.len() == 4));
+        assert!(outer[0].contains(&(1, 1)) && outer[1].contains(&(3, 3)));
+    }
+
+    #[test]
+    fn test_svg_styles_biomes() {
+        let biomes = Map::from_vec(vec![BiomeId::NONE, BiomeId(4)], 2, 1);
+        let elevation = Map::from_vec(vec![0.0, 1.0], 2, 1);
+        let svg = SvgExporterBuilder::new()
+            .scale(2.0)
+            .sea_level(0.5)
+            .biome(BiomeId(4), "Snowy Peaks", Rgba([250, 250, 255, 128]))
+            .build()
+            .to_svg(&biomes, &elevation, &[], &[]);
+
+        assert!(svg.contains("width=\"4\" height=\"2\" viewBox=\"0 0 2 1\""));
+        assert!(svg.contains(
+            "class=\"biome-snowy-peaks\" data-biome=\"4\" fill=\"#fafaff\" fill-opacity=\"0.502\""
+        ));
+        assert!(svg.contains("d=\"M1 0L2 0L2 1L1 1Z\""));
+    }
+
+    #[test]
+    fn test_rivers_widen_with_flow() {
+        let biomes = Map::from_vec(vec![BiomeId(1); 4], 2, 2);
+        let elevation = Map::from_vec(vec![1.0; 4], 2, 2);
+        let rivers = [
+            River {
+                points: vec![(0.5, 0.5), (1.5, 0.5)],
+                flow: 4,
+            },
+            River {
+                points: vec![(0.5, 1.5), (1.5, 1.5)],
+                flow: 1,
+            },
+        ];
+        let svg = SvgExporterBuilder::new()
+            .river(Rgba([0, 0, 255, 255]), 2.0)
+            .build()
+            .to_svg(&biomes, &elevation, &[], &rivers);
+
+        assert!(svg.contains("class=\"rivers\" fill=\"none\""));
+        assert!(svg.contains("stroke=\"#0000ff\""));
+        assert!(svg.contains("data-flow=\"4\" stroke-width=\"2.000\" d=\"M0.50 0.50L1.50 0.50\""));
+        assert!(svg.contains("data-flow=\"1\" stroke-width=\"1.000\""));
+    }
+}
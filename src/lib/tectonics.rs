@@ -0,0 +1,293 @@
+//! This module provides an elevation generator based on a simple
+//! simulation of plate tectonics.
+//!
+//! The map is split into plates by a Voronoi diagram that wraps
+//! around the east-west edges of the map. Each plate is either
+//! continental or oceanic and drifts in some direction. Where
+//! plates push into one another, mountain ranges, island arcs and
+//! ocean trenches form. Where they pull apart, rift valleys and
+//! mid-ocean ridges form.
+//!
+//! The [`TectonicsGenerator`] implements [`SimpleNoiseGenerator`],
+//! so it can be used as an elevation source on its own or mixed
+//! with the simplex noise generators.
+//!
+//! # Examples
+//!
+//! ```
+//! use ficture::map::{Map, MapMonad};
+//! use ficture::noise::SimpleNoiseGenerator;
+//! use ficture::tectonics::TectonicsGeneratorBuilder;
+//!
+//! let generator = TectonicsGeneratorBuilder::new(100, 50)
+//!     .plates(8)
+//!     .seed(42)
+//!     .build();
+//! let elevation = Map::return_single(0.0, 100, 50);
+//! let elevation = elevation.and_then_with_coordinates(|_, x, y| generator.generate(x, y));
+//! ```
+use std::f64::consts::PI;
+
+use noise::{NoiseFn, Simplex};
+
+use crate::{noise::SimpleNoiseGenerator, utils::Random};
+
+/// The elevation of the interior of an oceanic plate.
+const OCEANIC_ELEVATION: f64 = 0.0;
+/// The elevation of the interior of a continental plate.
+const CONTINENTAL_ELEVATION: f64 = 0.2;
+
+/// A single tectonic plate.
+struct Plate {
+    /// The center of the plate, in fractions of the map width.
+    center: (f64, f64),
+    /// The direction and speed that the plate is drifting.
+    velocity: (f64, f64),
+    /// Whether the plate carries a continent or ocean floor.
+    continental: bool,
+}
+
+impl Plate {
+    /// The elevation of the interior of the plate.
+    fn elevation(&self) -> f64 {
+        if self.continental {
+            CONTINENTAL_ELEVATION
+        } else {
+            OCEANIC_ELEVATION
+        }
+    }
+}
+
+/// Gets the offset from `from` to `to`, taking the shortest path
+/// around the east-west edges of the map.
+fn wrapped_offset(from: (f64, f64), to: (f64, f64)) -> (f64, f64) {
+    let dx = to.0 - from.0;
+
+    (dx - dx.round(), to.1 - from.1)
+}
+
+/// An elevation generator that raises and lowers the elevation
+/// along the boundaries between drifting tectonic plates.
+pub struct TectonicsGenerator {
+    width: usize,
+    plates: Vec<Plate>,
+    boundary_width: f64,
+    noise: Simplex,
+}
+
+impl TectonicsGenerator {
+    /// Gets the position of a cell in fractions of the map width.
+    /// The position is distorted by noise so that plate boundaries
+    /// are jagged rather than straight lines. The noise is sampled
+    /// on a cylinder so that the distortion wraps east-west.
+    fn position(&self, x: usize, y: usize) -> (f64, f64) {
+        let (frequency, amplitude) = (6.0, 0.05);
+        let scale_x = x as f64 / self.width as f64;
+        let scale_y = y as f64 / self.width as f64;
+        let angle = scale_x * 2.0 * PI;
+        let (circle_x, circle_z) = (angle.cos() / (2.0 * PI), angle.sin() / (2.0 * PI));
+        let point = [
+            frequency * circle_x,
+            frequency * scale_y,
+            frequency * circle_z,
+        ];
+
+        (
+            scale_x + amplitude * self.noise.get(point),
+            scale_y + amplitude * self.noise.get([point[0] + 31.7, point[1], point[2]]),
+        )
+    }
+}
+
+impl SimpleNoiseGenerator for TectonicsGenerator {
+    /// Creates an elevation from 0-1 at the coordinates `x` and `y`.
+    fn generate(&self, x: usize, y: usize) -> f64 {
+        let position = self.position(x, y);
+        let offsets: Vec<(f64, f64)> = self
+            .plates
+            .iter()
+            .map(|plate| wrapped_offset(plate.center, position))
+            .collect();
+        let distances: Vec<f64> = offsets
+            .iter()
+            .map(|offset| offset.0 * offset.0 + offset.1 * offset.1)
+            .collect();
+
+        // the cell belongs to the plate with the nearest center
+        let nearest = (0..self.plates.len())
+            .min_by(|a, b| distances[*a].total_cmp(&distances[*b]))
+            .unwrap_or(0);
+        let plate = &self.plates[nearest];
+        let mut base = plate.elevation();
+        let mut uplift = 0.0;
+
+        // every neighboring plate affects the cell based on how close
+        // the cell is to the boundary between the two plates, so that
+        // the elevation is continuous where boundaries meet
+        for (index, neighbor) in self.plates.iter().enumerate() {
+            if index == nearest {
+                continue;
+            }
+
+            // the distance from the cell to the boundary between the
+            // two plates, along the line joining their centers
+            let between = (
+                offsets[nearest].0 - offsets[index].0,
+                offsets[nearest].1 - offsets[index].1,
+            );
+            let length = (between.0 * between.0 + between.1 * between.1).sqrt();
+
+            if length == 0.0 {
+                continue;
+            }
+
+            let normal = (between.0 / length, between.1 / length);
+            let boundary_distance = (distances[index] - distances[nearest]) / (2.0 * length);
+
+            // how quickly the plates are moving towards one another
+            let convergence = (plate.velocity.0 - neighbor.velocity.0) * normal.0
+                + (plate.velocity.1 - neighbor.velocity.1) * normal.1;
+
+            let influence = (-(boundary_distance / self.boundary_width).powi(2)).exp();
+            let spread = (-(boundary_distance / (3.0 * self.boundary_width)).powi(2)).exp();
+
+            let effect = if convergence > 0.0 {
+                match (plate.continental, neighbor.continental) {
+                    // continents crumple into mountain ranges
+                    (true, _) => 0.6 * convergence,
+                    // ocean floor sinks beneath a continent into a trench
+                    (false, true) => -0.25 * convergence,
+                    // ocean floor sinking beneath ocean floor forms island arcs
+                    (false, false) => 0.3 * convergence,
+                }
+            } else if plate.continental {
+                // continents tear apart into rift valleys
+                0.15 * convergence
+            } else {
+                // ocean floor spreads apart along mid-ocean ridges
+                -0.04 * convergence
+            };
+
+            // ease the interiors of the plates into one another
+            base += 0.5 * spread * (neighbor.elevation() - plate.elevation());
+            uplift += influence * effect;
+        }
+
+        (base + uplift).clamp(0.0, 1.0)
+    }
+}
+
+/// A builder for the [`TectonicsGenerator`].
+pub struct TectonicsGeneratorBuilder {
+    width: usize,
+    height: usize,
+    plates: usize,
+    seed: u64,
+    continental_fraction: f64,
+    boundary_width: f64,
+}
+
+impl TectonicsGeneratorBuilder {
+    /// Creates the [`TectonicsGeneratorBuilder`].
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            plates: 12,
+            seed: 0,
+            continental_fraction: 0.4,
+            boundary_width: 0.02,
+        }
+    }
+
+    /// Sets the number of plates to split the map into.
+    pub fn plates(mut self, plates: usize) -> Self {
+        self.plates = plates;
+        self
+    }
+
+    /// Sets the seed used to place the plates and choose their
+    /// motion.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets the fraction of plates that carry continents.
+    pub fn continental_fraction(mut self, continental_fraction: f64) -> Self {
+        self.continental_fraction = continental_fraction;
+        self
+    }
+
+    /// Sets how far the effects of plate boundaries reach, as a
+    /// fraction of the width of the map.
+    pub fn boundary_width(mut self, boundary_width: f64) -> Self {
+        self.boundary_width = boundary_width;
+        self
+    }
+
+    /// Construct the [`TectonicsGenerator`] based on the
+    /// defined attributes.
+    pub fn build(self) -> TectonicsGenerator {
+        let mut random = Random::new(self.seed);
+        let aspect_ratio = self.height as f64 / self.width as f64;
+        let plates = (0..self.plates.max(1))
+            .map(|_| {
+                let center = (random.next_f64(), random.next_f64() * aspect_ratio);
+                let angle = random.next_f64() * 2.0 * PI;
+                let speed = 0.2 + 0.8 * random.next_f64();
+
+                Plate {
+                    center,
+                    velocity: (speed * angle.cos(), speed * angle.sin()),
+                    continental: random.next_f64() < self.continental_fraction,
+                }
+            })
+            .collect();
+
+        TectonicsGenerator {
+            width: self.width,
+            plates,
+            boundary_width: self.boundary_width,
+            noise: Simplex::new(random.next_u64() as u32),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_wrapped_offset() {
+        let (dx, dy) = wrapped_offset((0.95, 0.0), (0.05, 0.1));
+
+        assert!((dx - 0.1).abs() < 1e-9);
+        assert!((dy - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_converging_continents_form_mountains() {
+        // two continents drifting into one another
+        let generator = TectonicsGenerator {
+            width: 100,
+            plates: vec![
+                Plate {
+                    center: (0.25, 0.25),
+                    velocity: (1.0, 0.0),
+                    continental: true,
+                },
+                Plate {
+                    center: (0.75, 0.25),
+                    velocity: (-1.0, 0.0),
+                    continental: true,
+                },
+            ],
+            boundary_width: 0.02,
+            noise: Simplex::new(0),
+        };
+
+        assert!(generator.generate(50, 25) > generator.generate(25, 25));
+        assert!((0..100).all(|x| (0.0..=1.0).contains(&generator.generate(x, 25))));
+    }
+}
@@ -0,0 +1,314 @@
+//! This module provides analysis of the shape of the terrain, such
+//! as its slope and aspect, along with hillshading to give rendered
+//! maps a sense of relief.
+//!
+//! Slopes are found with Horn's method, which weighs the elevation
+//! of all eight neighbors of a cell. Like the rest of the crate, the
+//! terrain wraps around the east-west edges of the map.
+//!
+//! The shape of the terrain can also be exported for use in other
+//! tools, as a tangent-space normal map with [`normal_map`] or as
+//! maps of the slope and aspect with [`slope_map`] and
+//! [`aspect_map`].
+//!
+//! # Examples
+//!
+//! ```
+//! use ficture::map::{Map, MapMonad};
+//! use ficture::terrain::HillshadeBuilder;
+//!
+//! let elevation = Map::return_single(0.0, 10, 10);
+//! let elevation = elevation.and_then_with_coordinates(|_, x, _| x as f64 / 10.0);
+//!
+//! let hillshade = HillshadeBuilder::new()
+//!     .azimuth(315.0)
+//!     .altitude(45.0)
+//!     .build();
+//! let shade = hillshade.shade(&elevation);
+//! ```
+use image::{Rgb, Rgba};
+
+use crate::{
+    map::{Map, MapMonad},
+    utils::wrap,
+};
+
+/// Gets the rate that the elevation changes towards the east and
+/// towards the south at a cell, using Horn's method.
+pub fn surface_gradient(elevation: &Map<f64>, x: usize, y: usize) -> (f64, f64) {
+    let (width, height) = (elevation.width(), elevation.height());
+    let at = |dx: isize, dy: isize| {
+        let neighbor_x = wrap(x as isize + dx, width);
+        let neighbor_y = (y as isize + dy).clamp(0, height as isize - 1) as usize;
+
+        *elevation.get(neighbor_x, neighbor_y)
+    };
+
+    let east = at(1, -1) + 2.0 * at(1, 0) + at(1, 1);
+    let west = at(-1, -1) + 2.0 * at(-1, 0) + at(-1, 1);
+    let south = at(-1, 1) + 2.0 * at(0, 1) + at(1, 1);
+    let north = at(-1, -1) + 2.0 * at(0, -1) + at(1, -1);
+
+    ((east - west) / 8.0, (south - north) / 8.0)
+}
+
+/// Gets the slope, in radians from horizontal, and the aspect, in
+/// radians clockwise from north, of the direction that a surface
+/// with the given gradient faces.
+pub fn slope_and_aspect(gradient: (f64, f64)) -> (f64, f64) {
+    let (dz_dx, dz_dy) = gradient;
+    let slope = (dz_dx * dz_dx + dz_dy * dz_dy).sqrt().atan();
+    // the surface faces downhill, against the gradient
+    let aspect = (-dz_dx).atan2(dz_dy).rem_euclid(std::f64::consts::TAU);
+
+    (slope, aspect)
+}
+
+/// Gets the normal of a surface with the given gradient, after the
+/// elevation is stretched by `exaggeration`. The normal is in tangent
+/// space, with x running east, y running north and z running up.
+pub fn surface_normal(gradient: (f64, f64), exaggeration: f64) -> [f64; 3] {
+    let (dz_dx, dz_dy) = gradient;
+    // the gradient runs south, while y runs north
+    let normal = [-dz_dx * exaggeration, dz_dy * exaggeration, 1.0];
+    let length = normal.iter().map(|axis| axis * axis).sum::<f64>().sqrt();
+
+    normal.map(|axis| axis / length)
+}
+
+/// Gets a tangent-space normal map of the terrain, with each axis of
+/// the normal stored in a channel from 0-255 where 128 is 0. Green
+/// points north, which matches the convention used by OpenGL.
+pub fn normal_map(elevation: &Map<f64>, exaggeration: f64) -> Map<Rgb<u8>> {
+    Map::return_single(Rgb([0, 0, 0]), elevation.width(), elevation.height())
+        .and_then_with_coordinates(|_, x, y| {
+            let normal = surface_normal(surface_gradient(elevation, x, y), exaggeration);
+
+            Rgb(normal.map(|axis| ((axis + 1.0) / 2.0 * 255.0).round() as u8))
+        })
+}
+
+/// Gets the slope of every cell, after the elevation is stretched by
+/// `exaggeration`, from 0 for flat ground to 1 for a vertical cliff.
+pub fn slope_map(elevation: &Map<f64>, exaggeration: f64) -> Map<f64> {
+    Map::return_single(0.0, elevation.width(), elevation.height()).and_then_with_coordinates(
+        |_, x, y| {
+            let (dz_dx, dz_dy) = surface_gradient(elevation, x, y);
+            let (slope, _) = slope_and_aspect((dz_dx * exaggeration, dz_dy * exaggeration));
+
+            slope / std::f64::consts::FRAC_PI_2
+        },
+    )
+}
+
+/// Gets the aspect of every cell, from 0-1 clockwise from north.
+/// Flat ground faces north.
+pub fn aspect_map(elevation: &Map<f64>) -> Map<f64> {
+    Map::return_single(0.0, elevation.width(), elevation.height()).and_then_with_coordinates(
+        |_, x, y| {
+            let (_, aspect) = slope_and_aspect(surface_gradient(elevation, x, y));
+
+            aspect / std::f64::consts::TAU
+        },
+    )
+}
+
+/// A structure for lighting terrain as if by the sun, which gives
+/// a sense of relief to otherwise flat colors.
+pub struct Hillshade {
+    /// The direction of the sun, in radians clockwise from north.
+    azimuth: f64,
+    /// The height of the sun, in radians above the horizon.
+    altitude: f64,
+    exaggeration: f64,
+    multidirectional: bool,
+    strength: f64,
+}
+
+impl Hillshade {
+    /// Gets how brightly a surface is lit by a sun in the direction
+    /// `azimuth`.
+    fn illuminate(&self, slope: f64, aspect: f64, azimuth: f64) -> f64 {
+        let zenith = std::f64::consts::FRAC_PI_2 - self.altitude;
+
+        (zenith.cos() * slope.cos() + zenith.sin() * slope.sin() * (azimuth - aspect).cos())
+            .max(0.0)
+    }
+
+    /// Gets how brightly a cell is lit, from 0-1, given the gradient
+    /// of the terrain at that cell.
+    pub fn shade_gradient(&self, gradient: (f64, f64)) -> f64 {
+        let gradient = (
+            gradient.0 * self.exaggeration,
+            gradient.1 * self.exaggeration,
+        );
+        let (slope, aspect) = slope_and_aspect(gradient);
+
+        if !self.multidirectional {
+            return self.illuminate(slope, aspect, self.azimuth);
+        }
+
+        // light from several directions around the sun, with each
+        // weighted by how much it lights the slope from the side,
+        // so that slopes facing directly away from the sun still
+        // show their shape
+        let (mut total, mut total_weight) = (0.0, 0.0);
+
+        for offset in [-90.0_f64, -45.0, 0.0, 45.0] {
+            let azimuth = self.azimuth + offset.to_radians();
+            let weight = (aspect - azimuth).sin().powi(2) + 1e-3;
+
+            total += weight * self.illuminate(slope, aspect, azimuth);
+            total_weight += weight;
+        }
+
+        total / total_weight
+    }
+
+    /// Gets how brightly every cell of a map is lit, from 0-1.
+    pub fn shade(&self, elevation: &Map<f64>) -> Map<f64> {
+        Map::return_single(0.0, elevation.width(), elevation.height()).and_then_with_coordinates(
+            |_, x, y| self.shade_gradient(surface_gradient(elevation, x, y)),
+        )
+    }
+
+    /// Gets how brightly flat ground is lit.
+    pub fn flat_shade(&self) -> f64 {
+        self.altitude.sin()
+    }
+
+    /// Darkens or lightens a color by the shade of its cell. Flat
+    /// ground keeps its color, and the strength of the hillshade
+    /// determines how far the color moves towards its shaded color.
+    pub fn apply(&self, color: Rgba<u8>, shade: f64) -> Rgba<u8> {
+        let flat_shade = self.flat_shade().max(f64::EPSILON);
+        let factor = 1.0 + (shade / flat_shade - 1.0) * self.strength;
+        let channel = |i: usize| (color.0[i] as f64 * factor).round().clamp(0.0, 255.0) as u8;
+
+        Rgba([channel(0), channel(1), channel(2), color.0[3]])
+    }
+}
+
+/// A builder for the [`Hillshade`].
+pub struct HillshadeBuilder {
+    azimuth: f64,
+    altitude: f64,
+    exaggeration: f64,
+    multidirectional: bool,
+    strength: f64,
+}
+
+impl HillshadeBuilder {
+    /// Creates the [`HillshadeBuilder`].
+    pub fn new() -> Self {
+        Self {
+            azimuth: 315.0,
+            altitude: 45.0,
+            exaggeration: 50.0,
+            multidirectional: false,
+            strength: 0.5,
+        }
+    }
+
+    /// Sets the direction of the sun, in degrees clockwise from
+    /// north.
+    pub fn azimuth(mut self, azimuth: f64) -> Self {
+        self.azimuth = azimuth;
+        self
+    }
+
+    /// Sets the height of the sun, in degrees above the horizon.
+    pub fn altitude(mut self, altitude: f64) -> Self {
+        self.altitude = altitude;
+        self
+    }
+
+    /// Sets how much the elevation is stretched before it is lit.
+    /// Elevation is measured from 0-1 while cells are 1 apart, so
+    /// terrain is very flat without exaggeration.
+    pub fn exaggeration(mut self, exaggeration: f64) -> Self {
+        self.exaggeration = exaggeration;
+        self
+    }
+
+    /// Sets whether the terrain is lit from several directions
+    /// around the sun rather than from the sun alone.
+    pub fn multidirectional(mut self, multidirectional: bool) -> Self {
+        self.multidirectional = multidirectional;
+        self
+    }
+
+    /// Sets how strongly shading changes colors, from 0-1.
+    pub fn strength(mut self, strength: f64) -> Self {
+        self.strength = strength;
+        self
+    }
+
+    /// Construct the [`Hillshade`] based on the
+    /// defined attributes.
+    pub fn build(self) -> Hillshade {
+        Hillshade {
+            azimuth: self.azimuth.to_radians(),
+            altitude: self.altitude.to_radians(),
+            exaggeration: self.exaggeration,
+            multidirectional: self.multidirectional,
+            strength: self.strength,
+        }
+    }
+}
+
+impl Default for HillshadeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_slope_faces_downhill() {
+        let flat = Map::return_single(0.5, 10, 10);
+        let valley = Map::return_single(0.0, 10, 10)
+            .and_then_with_coordinates(|_, x, _| (x as f64 - 5.0).abs());
+        // the east side of the valley rises towards the east, so it
+        // faces west
+        let (slope, aspect) = slope_and_aspect(surface_gradient(&valley, 7, 5));
+
+        assert_eq!(surface_gradient(&flat, 5, 5), (0.0, 0.0));
+        assert!((slope - std::f64::consts::FRAC_PI_4).abs() < 1e-9);
+        assert!((aspect - 1.5 * std::f64::consts::PI).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_normal_map_points_away_from_slopes() {
+        let ramp =
+            Map::return_single(0.0, 10, 10).and_then_with_coordinates(|_, _, y| y as f64 / 10.0);
+        let normals = normal_map(&ramp, 10.0);
+        let slopes = slope_map(&ramp, 10.0);
+        let aspects = aspect_map(&ramp);
+        // the ramp rises towards the south, so it faces north
+        let Rgb([red, green, blue]) = *normals.get(5, 5);
+
+        assert_eq!(red, 128);
+        assert!(green > 128 && blue > 128);
+        assert!((slopes.get(5, 5) - 0.5).abs() < 1e-9);
+        assert_eq!(*aspects.get(5, 5), 0.0);
+    }
+
+    #[test]
+    fn test_slopes_facing_the_sun_are_brighter() {
+        let hillshade = HillshadeBuilder::new().azimuth(270.0).strength(1.0).build();
+        let facing_west = hillshade.shade_gradient((0.01, 0.0));
+        let facing_east = hillshade.shade_gradient((-0.01, 0.0));
+        let flat = hillshade.shade_gradient((0.0, 0.0));
+
+        assert!(facing_west > flat && flat > facing_east);
+        assert!((flat - hillshade.flat_shade()).abs() < 1e-9);
+        assert_eq!(
+            hillshade.apply(Rgba([100, 100, 100, 255]), flat),
+            Rgba([100, 100, 100, 255])
+        );
+    }
+}
@@ -0,0 +1,420 @@
+//! This module provides a [`TileRenderer`] for rendering a map as a
+//! pyramid of `z/x/y.png` tiles that can be browsed in a web map
+//! viewer, along with a small HTML viewer for browsing them locally.
+//!
+//! Tiles are generated directly from the noise generators at the
+//! resolution of each zoom level rather than by downsampling a single
+//! huge map, so zooming in reveals more detail instead of larger
+//! pixels. The world is twice as wide as it is tall, so zoom level
+//! `z` has `2^(z + 1)` columns and `2^z` rows of tiles. Columns wrap
+//! around from east to west like the rest of the map.
+//!
+//! Tiles are rendered from the `elevation_noise`, `moisture_noise`,
+//! `elevation_tectonics` and `elevation_mask` sections of the config,
+//! colored by the `default` biome map and the `ocean` gradient. Steps
+//! that need the whole map at once, such as reshaping landmasses,
+//! moisture models, hillshading and contours, are skipped, since a
+//! tile only knows about its own cells.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use ficture::config::Config;
+//! use ficture::tiles::{export_tiles, TileRenderer};
+//!
+//! let config = Config::from_file("config/config.yaml").unwrap();
+//! let renderer = TileRenderer::new(config, 0.05, 256).unwrap();
+//!
+//! export_tiles(&renderer, "tiles", 3).unwrap();
+//! ```
+use std::path::Path;
+
+use image::{Rgba, RgbaImage};
+use thiserror::Error;
+
+use crate::{
+    color::{ColorEvaluator, ColorFunc},
+    config::{Config, ConfigError},
+    image::pixel_map_to_rgba_image,
+    map::{Map, MapMonad},
+    mask::MaskLayer,
+    noise::{SimpleNoiseGenerator, SimplexNoiseGeneratorBuilder},
+    utils::{normalize, wrap},
+};
+
+/// The error type for tile errors.
+#[derive(Error, Debug)]
+pub enum TileError {
+    #[error("missing config (expected {0} to be defined in the config file, but found nothing)")]
+    Undefined(&'static str),
+    #[error("failed to write tiles: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to save tile: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("failed to load config: {0}")]
+    Config(#[from] ConfigError),
+}
+
+/// A result type for [`TileError`].
+pub type TileResult<T> = Result<T, TileError>;
+
+/// The generators for a single zoom level, sized to the resolution of
+/// the whole world at that level.
+pub struct TileLevel {
+    zoom: u32,
+    width: usize,
+    height: usize,
+    elevation: Box<dyn SimpleNoiseGenerator + Send + Sync>,
+    moisture: Box<dyn SimpleNoiseGenerator + Send + Sync>,
+    tectonics: Option<MaskLayer>,
+    mask: Option<MaskLayer>,
+}
+
+impl TileLevel {
+    /// The zoom level of the generators.
+    pub fn zoom(&self) -> u32 {
+        self.zoom
+    }
+
+    /// Generates the raw elevation and moisture noise of a cell.
+    fn noise(&self, x: usize, y: usize) -> (f64, f64) {
+        (self.elevation.generate(x, y), self.moisture.generate(x, y))
+    }
+}
+
+/// A structure for rendering the tiles of a map at any zoom level.
+pub struct TileRenderer {
+    config: Config,
+    evaluator: ColorEvaluator,
+    ocean: ColorFunc,
+    sea_level: f64,
+    tile_size: usize,
+    elevation_range: (f64, f64),
+    moisture_range: (f64, f64),
+}
+
+impl TileRenderer {
+    /// Creates a [`TileRenderer`] from a config, rendering square
+    /// tiles `tile_size` pixels wide.
+    ///
+    /// Noise is normalized using the range of the noise over the
+    /// lowest zoom level, so that every level is normalized the same
+    /// way and tiles line up between levels.
+    pub fn new(config: Config, sea_level: f64, tile_size: usize) -> TileResult<Self> {
+        let evaluator = config
+            .get_color_evaluator("default")
+            .ok_or(TileError::Undefined("the default color evaluator"))?;
+        let ocean = config
+            .get_color_func("ocean")
+            .ok_or(TileError::Undefined("the ocean gradient"))?;
+        let mut renderer = Self {
+            config,
+            evaluator,
+            ocean,
+            sea_level,
+            tile_size: tile_size.max(1),
+            elevation_range: (0.0, 1.0),
+            moisture_range: (0.0, 1.0),
+        };
+        let level = renderer.level(0)?;
+        let noise = Map::return_single((0.0, 0.0), level.width, level.height)
+            .and_then_with_coordinates(|_, x, y| level.noise(x, y));
+        let (min_elevation, max_elevation, min_moisture, max_moisture) = noise.iter().fold(
+            (f64::MAX, f64::MIN, f64::MAX, f64::MIN),
+            |(min_elevation, max_elevation, min_moisture, max_moisture), (elevation, moisture)| {
+                (
+                    min_elevation.min(*elevation),
+                    max_elevation.max(*elevation),
+                    min_moisture.min(*moisture),
+                    max_moisture.max(*moisture),
+                )
+            },
+        );
+
+        renderer.elevation_range = (min_elevation, max_elevation);
+        renderer.moisture_range = (min_moisture, max_moisture);
+
+        Ok(renderer)
+    }
+
+    /// The width and height of every tile in pixels.
+    pub fn tile_size(&self) -> usize {
+        self.tile_size
+    }
+
+    /// The number of columns and rows of tiles at a zoom level.
+    pub fn tile_counts(&self, zoom: u32) -> (usize, usize) {
+        (1 << (zoom + 1), 1 << zoom)
+    }
+
+    /// Creates the generators for a zoom level. Creating them can be
+    /// slow at high zoom levels, so they should be reused for every
+    /// tile of the level.
+    pub fn level(&self, zoom: u32) -> TileResult<TileLevel> {
+        let (columns, rows) = self.tile_counts(zoom);
+        let (width, height) = (columns * self.tile_size, rows * self.tile_size);
+        let elevation = self
+            .config
+            .get_noise_generator::<SimplexNoiseGeneratorBuilder>("elevation_noise", width, height)
+            .ok_or(TileError::Undefined("the elevation_noise noise generator"))?;
+        let moisture = self
+            .config
+            .get_noise_generator::<SimplexNoiseGeneratorBuilder>("moisture_noise", width, height)
+            .ok_or(TileError::Undefined("the moisture_noise noise generator"))?;
+
+        Ok(TileLevel {
+            zoom,
+            width,
+            height,
+            elevation,
+            moisture,
+            tectonics: self
+                .config
+                .get_tectonics("elevation_tectonics", width, height),
+            mask: self.config.get_mask("elevation_mask", width, height)?,
+        })
+    }
+
+    /// Renders the tile in column `x` and row `y` of a zoom level.
+    /// Columns wrap around the world, while rows outside of it return
+    /// `None`.
+    pub fn render_tile(&self, level: &TileLevel, x: i64, y: i64) -> Option<RgbaImage> {
+        let (columns, rows) = self.tile_counts(level.zoom);
+
+        if y < 0 || y >= rows as i64 {
+            return None;
+        }
+
+        let left = wrap(x as isize, columns) * self.tile_size;
+        let top = y as usize * self.tile_size;
+        let tile = Map::return_single(Rgba([0, 0, 0, 0]), self.tile_size, self.tile_size)
+            .and_then_with_coordinates(|_, tile_x, tile_y| {
+                let (x, y) = (left + tile_x, top + tile_y);
+                let (elevation, moisture) = level.noise(x, y);
+                let (min_elevation, max_elevation) = self.elevation_range;
+                let (min_moisture, max_moisture) = self.moisture_range;
+                let elevation = normalize(elevation, min_elevation, max_elevation).clamp(0.0, 1.0);
+                let moisture = normalize(moisture, min_moisture, max_moisture).clamp(0.0, 1.0);
+                let elevation = match &level.tectonics {
+                    Some(tectonics) => tectonics.apply(elevation, x, y),
+                    None => elevation,
+                };
+                let elevation = match &level.mask {
+                    Some(mask) => mask.apply(elevation, x, y),
+                    None => elevation,
+                };
+
+                if elevation < self.sea_level {
+                    (self.ocean)(normalize(elevation, 0.0, self.sea_level))
+                } else {
+                    self.evaluator.evaluate(elevation, moisture)
+                }
+            });
+
+        Some(tile.extract(pixel_map_to_rgba_image))
+    }
+}
+
+/// Creates a self contained HTML page for browsing tiles saved next
+/// to it as `z/x/y.png`, with dragging to pan and scrolling to zoom.
+/// When `live` is set, the page polls `version` next to it every
+/// second and reloads the tiles whenever the version changes.
+pub fn viewer_html(tile_size: usize, max_zoom: u32, live: bool) -> String {
+    format!(
+        r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>ficture</title>
+<style>
+html, body {{ margin: 0; height: 100%; overflow: hidden; background: #1b1b1b; }}
+#map {{ position: absolute; inset: 0; cursor: grab; touch-action: none; }}
+#map img {{ position: absolute; width: {tile_size}px; height: {tile_size}px; user-select: none; -webkit-user-drag: none; }}
+</style>
+</head>
+<body>
+<div id="map"></div>
+<script>
+const tileSize = {tile_size};
+const maxZoom = {max_zoom};
+const live = {live};
+const map = document.getElementById("map");
+// the center of the view, in pixels of the world at zoom level 0
+let zoom = 0;
+let centerX = tileSize;
+let centerY = tileSize / 2;
+let drag = null;
+let version = 0;
+
+function clampCenter() {{
+  centerX = ((centerX % (2 * tileSize)) + 2 * tileSize) % (2 * tileSize);
+  centerY = Math.min(tileSize, Math.max(0, centerY));
+}}
+
+function render() {{
+  const scale = 2 ** zoom;
+  const columns = 2 ** (zoom + 1);
+  const rows = 2 ** zoom;
+  const left = centerX * scale - map.clientWidth / 2;
+  const top = centerY * scale - map.clientHeight / 2;
+  const wanted = new Set();
+  const firstRow = Math.max(0, Math.floor(top / tileSize));
+  const lastRow = Math.min(rows - 1, Math.floor((top + map.clientHeight) / tileSize));
+
+  for (let y = firstRow; y <= lastRow; y++) {{
+    for (let x = Math.floor(left / tileSize); x <= Math.floor((left + map.clientWidth) / tileSize); x++) {{
+      // columns past either edge of the world wrap around to the other
+      const column = ((x % columns) + columns) % columns;
+      const key = version + ":" + zoom + "/" + x + "/" + y;
+      let tile = map.querySelector('[data-key="' + key + '"]');
+
+      if (!tile) {{
+        tile = document.createElement("img");
+        tile.dataset.key = key;
+        tile.src = zoom + "/" + column + "/" + y + ".png" + (version ? "?v=" + version : "");
+        map.appendChild(tile);
+      }}
+
+      tile.style.left = (x * tileSize - left) + "px";
+      tile.style.top = (y * tileSize - top) + "px";
+      wanted.add(key);
+    }}
+  }}
+
+  for (const tile of [...map.children]) {{
+    if (!wanted.has(tile.dataset.key)) {{
+      tile.remove();
+    }}
+  }}
+}}
+
+map.addEventListener("pointerdown", (event) => {{
+  drag = {{ x: event.clientX, y: event.clientY }};
+  map.setPointerCapture(event.pointerId);
+}});
+map.addEventListener("pointermove", (event) => {{
+  if (!drag) {{
+    return;
+  }}
+
+  centerX -= (event.clientX - drag.x) / 2 ** zoom;
+  centerY -= (event.clientY - drag.y) / 2 ** zoom;
+  drag = {{ x: event.clientX, y: event.clientY }};
+  clampCenter();
+  render();
+}});
+map.addEventListener("pointerup", () => {{
+  drag = null;
+}});
+map.addEventListener("wheel", (event) => {{
+  event.preventDefault();
+
+  const next = Math.min(maxZoom, Math.max(0, zoom + (event.deltaY < 0 ? 1 : -1)));
+
+  if (next === zoom) {{
+    return;
+  }}
+
+  // keep the point under the cursor in place while zooming
+  const offsetX = event.clientX - map.clientWidth / 2;
+  const offsetY = event.clientY - map.clientHeight / 2;
+
+  centerX += offsetX / 2 ** zoom - offsetX / 2 ** next;
+  centerY += offsetY / 2 ** zoom - offsetY / 2 ** next;
+  zoom = next;
+  clampCenter();
+  render();
+}}, {{ passive: false }});
+window.addEventListener("resize", render);
+
+if (live) {{
+  setInterval(async () => {{
+    try {{
+      const next = Number(await (await fetch("version")).text());
+
+      if (next !== version) {{
+        version = next;
+        render();
+      }}
+    }} catch (error) {{
+      // the server may be restarting, so try again next time
+    }}
+  }}, 1000);
+}}
+
+render();
+</script>
+</body>
+</html>
+"##
+    )
+}
+
+/// Saves every tile from zoom level 0 to `max_zoom` to `directory` as
+/// `z/x/y.png`, along with an `index.html` viewer for browsing them.
+pub fn export_tiles(
+    renderer: &TileRenderer,
+    directory: impl AsRef<Path>,
+    max_zoom: u32,
+) -> TileResult<()> {
+    let directory = directory.as_ref();
+
+    for zoom in 0..=max_zoom {
+        let level = renderer.level(zoom)?;
+        let (columns, rows) = renderer.tile_counts(zoom);
+
+        for x in 0..columns {
+            let column = directory.join(zoom.to_string()).join(x.to_string());
+
+            std::fs::create_dir_all(&column)?;
+
+            for y in 0..rows {
+                if let Some(tile) = renderer.render_tile(&level, x as i64, y as i64) {
+                    tile.save(column.join(format!("{y}.png")))?;
+                }
+            }
+        }
+    }
+
+    std::fs::write(
+        directory.join("index.html"),
+        viewer_html(renderer.tile_size(), max_zoom, false),
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tiles_wrap_east_west() {
+        let config = Config::from_file("config/config.yaml").unwrap();
+        let renderer = TileRenderer::new(config, 0.05, 16).unwrap();
+        let level = renderer.level(1).unwrap();
+        let first = renderer.render_tile(&level, 0, 0).unwrap();
+        let last = renderer.render_tile(&level, 3, 1).unwrap();
+
+        assert_eq!(renderer.tile_counts(1), (4, 2));
+        assert_eq!(renderer.render_tile(&level, 4, 0).unwrap(), first);
+        assert_eq!(renderer.render_tile(&level, -1, 1).unwrap(), last);
+        assert!(renderer.render_tile(&level, 0, 2).is_none());
+    }
+
+    #[test]
+    fn test_zoom_levels_line_up() {
+        let config = Config::from_file("config/config.yaml").unwrap();
+        let renderer = TileRenderer::new(config, 0.05, 16).unwrap();
+        let coarse = renderer
+            .render_tile(&renderer.level(0).unwrap(), 0, 0)
+            .unwrap();
+        let fine = renderer
+            .render_tile(&renderer.level(1).unwrap(), 0, 0)
+            .unwrap();
+
+        // the top left pixel of a tile covers the same spot of the
+        // world at every zoom level
+        assert_eq!(coarse.get_pixel(0, 0), fine.get_pixel(0, 0));
+    }
+}
@@ -6,3 +6,37 @@
 pub fn normalize(value: f64, min: f64, max: f64) -> f64 {
     (value - min) / (max - min)
 }
+
+/// Wraps an x coordinate that may have stepped off either the
+/// east or west edge of a map back into the range 0-width.
+pub fn wrap(x: isize, width: usize) -> usize {
+    x.rem_euclid(width as isize) as usize
+}
+
+/// A small pseudo-random number generator (SplitMix64) for
+/// generation steps that need reproducible randomness from a seed.
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    /// Creates a [`Random`] from a seed.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Gets the next random integer.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Gets the next random value from 0-1.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}